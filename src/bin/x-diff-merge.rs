@@ -0,0 +1,46 @@
+//! Git merge driver merging XML files with [x_diff_rs::merge::merge].
+//!
+//! ```text
+//! x-diff-merge %O %A %B
+//! ```
+//!
+//! The merged document is written to the `%A` file. The exit code is 0 if the merge is clean, 1 if
+//! there are conflicts, which are marked in the merged document, and 2 on errors.
+
+use std::{fs, process::ExitCode};
+
+use x_diff_rs::{merge::merge, tree::XTree};
+
+const USAGE: &str = "usage: x-diff-merge <base> <ours> <theirs>";
+
+fn run(base: &str, ours: &str, theirs: &str) -> Result<bool, String> {
+    let read = |path: &str| fs::read_to_string(path).map_err(|e| format!("{path}: {e}"));
+    let (base_text, ours_text, theirs_text) = (read(base)?, read(ours)?, read(theirs)?);
+    let parse = |text, path: &str| XTree::parse(text).map_err(|e| format!("{path}: {e}"));
+    let base_tree = parse(&base_text, base)?;
+    let ours_tree = parse(&ours_text, ours)?;
+    let theirs_tree = parse(&theirs_text, theirs)?;
+
+    let merge = merge(&base_tree, &ours_tree, &theirs_tree);
+    for conflict in &merge.conflicts {
+        eprintln!("{ours}: {conflict}");
+    }
+    fs::write(ours, &merge.text).map_err(|e| format!("{ours}: {e}"))?;
+    Ok(merge.is_clean())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [base, ours, theirs] = &args[..] else {
+        eprintln!("{USAGE}");
+        return ExitCode::from(2);
+    };
+    match run(base, ours, theirs) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(e) => {
+            eprintln!("x-diff-merge: {e}");
+            ExitCode::from(2)
+        }
+    }
+}
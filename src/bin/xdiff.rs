@@ -0,0 +1,392 @@
+//! Compare two XML files.
+//!
+//! ```text
+//! xdiff [options] <file1> <file2>
+//! xdiff [options] <dir1> <dir2>
+//! xdiff [options] --git <path> <old-file> <old-hex> <old-mode> <new-file> <new-hex> <new-mode> [<new-path> <message>]
+//! xdiff [options] --git <path>
+//! xdiff [options] --textconv <file>
+//! ```
+//!
+//! The exit code is 0 if the files are the same, 1 if they are different and 2 on errors, like
+//! `diff(1)`. With `--git`, or with the arguments of `GIT_EXTERNAL_DIFF`, the exit code is 0 for
+//! different files too, as git stops on a failing external diff. Git adds the new path and a message
+//! for renamed and copied files, and gives only the path of unmerged files. `/dev/null` stands for
+//! a missing file: all the nodes of the other file are shown as inserted or deleted.
+//!
+//! Two directories are compared file by file, see [x_diff_rs::dir::diff_dirs], and a report of the
+//! changed, added and removed XML files is printed.
+//!
+//! With `--textconv`, the file is written in a canonical form for the line diff of git: one node
+//! per line, with the attributes and children sorted unless `--ordered` is given.
+
+use std::{
+    fs,
+    io::{self, IsTerminal, Write},
+    path::Path,
+    process::ExitCode,
+};
+
+use termcolor::{ColorChoice, StandardStream};
+use x_diff_rs::{
+    comparator::NumericTolerance,
+    diff::{ChildOrder, DiffOptions, diff_with_options},
+    dir::{DirDiffOptions, diff_dirs_with_options},
+    json::{delete_document_to_json, insert_document_to_json, to_json},
+    selector::Selector,
+    tree::{
+        XNode, XNodeName, XTree,
+        print::{PrintTreeDiffOptions, write_deleted_tree, write_inserted_tree, write_tree_diff},
+    },
+    xml_patch::to_xml_patch,
+};
+
+const USAGE: &str = "usage: xdiff [options] <file1> <file2>
+       xdiff [options] <dir1> <dir2>
+       xdiff [options] --git <path> <old-file> <old-hex> <old-mode> <new-file> <new-hex> <new-mode>
+             [<new-path> <message>]
+       xdiff [options] --git <path>
+       xdiff [options] --textconv <file>
+
+Compare two XML files, or the XML files of two directories, regardless of the order of their
+children.
+
+options:
+  --git              compare the files given by git as GIT_EXTERNAL_DIFF or diff.<driver>.command
+  --textconv         write the file in a canonical form, for diff.<driver>.textconv
+  --format <format>  print the tree, the list of edit operations, a JSON array of the edits
+                     or an XML patch (RFC 5261): tree, edits, json or xml-patch
+                     [default: tree]
+  -e, --edits        same as --format edits
+  --color <when>     colour the tree: auto, always or never [default: auto]
+  --namespace        print the namespaces of the nodes
+  --ordered          compare children in order
+  --detect-moves     report moved subtrees as moves
+  --ignore <glob>    ignore the nodes whose path matches a glob, e.g. '**/@timestamp'
+  --tolerance <tol>  treat the numbers differing by at most tol as equal, e.g. 1e-9
+  --relative-tolerance <tol>
+                     treat the numbers differing by at most tol times the largest of their
+                     magnitudes as equal, e.g. 0.01
+  -h, --help         print this help";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Diff,
+    Git,
+    Textconv,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Tree,
+    Edits,
+    Json,
+    XmlPatch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Color {
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Debug)]
+struct Args {
+    mode: Mode,
+    format: Format,
+    color: Color,
+    namespace: bool,
+    ordered: bool,
+    diff_options: DiffOptions,
+    files: Vec<String>,
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Option<Args>, String> {
+    let mut parsed = Args {
+        mode: Mode::Diff,
+        format: Format::Tree,
+        color: Color::Auto,
+        namespace: false,
+        ordered: false,
+        diff_options: DiffOptions::default(),
+        files: Vec::new(),
+    };
+    let mut tolerance: Option<NumericTolerance> = None;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--git" => parsed.mode = Mode::Git,
+            "--textconv" => parsed.mode = Mode::Textconv,
+            "-e" | "--edits" => parsed.format = Format::Edits,
+            "--format" => {
+                parsed.format = match args.next().as_deref() {
+                    Some("tree") => Format::Tree,
+                    Some("edits") => Format::Edits,
+                    Some("json") => Format::Json,
+                    Some("xml-patch") => Format::XmlPatch,
+                    _ => return Err("--format expects tree, edits, json or xml-patch".to_string()),
+                }
+            }
+            "--color" => {
+                parsed.color = match args.next().as_deref() {
+                    Some("auto") => Color::Auto,
+                    Some("always") => Color::Always,
+                    Some("never") => Color::Never,
+                    _ => return Err("--color expects auto, always or never".to_string()),
+                }
+            }
+            "--namespace" => parsed.namespace = true,
+            "--ordered" => {
+                parsed.ordered = true;
+                parsed.diff_options = parsed.diff_options.with_child_order(ChildOrder::Ordered)
+            }
+            "--detect-moves" => parsed.diff_options = parsed.diff_options.with_move_detection(true),
+            "--ignore" => {
+                let Some(pattern) = args.next() else {
+                    return Err("--ignore expects a glob pattern".to_string());
+                };
+                parsed.diff_options = parsed.diff_options.with_ignored(Selector::Glob(pattern))
+            }
+            "--tolerance" | "--relative-tolerance" => {
+                let Some(value) = args
+                    .next()
+                    .and_then(|t| t.parse::<f64>().ok())
+                    .filter(|t| *t >= 0.0)
+                else {
+                    return Err(format!("{arg} expects a non-negative number"));
+                };
+                let numeric = tolerance.take().unwrap_or_default();
+                tolerance = Some(if arg == "--tolerance" {
+                    numeric.with_absolute(value)
+                } else {
+                    numeric.with_relative(value)
+                });
+            }
+            _ if arg.starts_with('-') && arg != "-" => {
+                return Err(format!("unknown option {arg}"));
+            }
+            _ => parsed.files.push(arg),
+        }
+    }
+    if let Some(tolerance) = tolerance {
+        parsed.diff_options = parsed.diff_options.with_numeric_tolerance(tolerance);
+    }
+    // called as GIT_EXTERNAL_DIFF, which also sets GIT_DIFF_PATH_TOTAL
+    let external_diff = std::env::var_os("GIT_DIFF_PATH_TOTAL").is_some();
+    if parsed.mode == Mode::Diff
+        && (matches!(parsed.files.len(), 7 | 9) || (external_diff && parsed.files.len() == 1))
+    {
+        parsed.mode = Mode::Git;
+    }
+    let (expected, message) = match parsed.mode {
+        Mode::Diff => (&[2][..], "2"),
+        Mode::Git => (&[1, 7, 9][..], "1, 7 or 9"),
+        Mode::Textconv => (&[1][..], "1"),
+    };
+    if !expected.contains(&parsed.files.len()) {
+        return Err(format!("expected {message} arguments"));
+    }
+    Ok(Some(parsed))
+}
+
+/// Read a file, `None` for `/dev/null`.
+fn read(path: &str) -> Result<Option<String>, String> {
+    let text = if path == "/dev/null" {
+        return Ok(None);
+    } else if path == "-" {
+        io::read_to_string(io::stdin())
+    } else {
+        fs::read_to_string(path)
+    };
+    text.map(Some).map_err(|e| format!("{path}: {e}"))
+}
+
+fn parse<'doc>(text: &'doc Option<String>, path: &str) -> Result<Option<XTree<'doc>>, String> {
+    text.as_deref()
+        .map(XTree::parse)
+        .transpose()
+        .map_err(|e| format!("{path}: {e}"))
+}
+
+fn output_error(result: io::Result<()>) -> Result<(), String> {
+    match result {
+        // e.g. piped to `head`
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        Err(e) => Err(format!("stdout: {e}")),
+        Ok(()) => Ok(()),
+    }
+}
+
+/// Compare the files and print the difference. Returns whether they are different.
+fn compare(path1: &str, path2: &str, header: Option<String>, args: &Args) -> Result<bool, String> {
+    let (text1, text2) = (read(path1)?, read(path2)?);
+    let (tree1, tree2) = (parse(&text1, path1)?, parse(&text2, path2)?);
+    let diff = match (&tree1, &tree2) {
+        (Some(tree1), Some(tree2)) => {
+            let diff = diff_with_options(tree1, tree2, args.diff_options.clone());
+            if diff.is_empty() {
+                return Ok(false);
+            }
+            Some(diff)
+        }
+        (None, None) => return Ok(false),
+        _ if args.format == Format::XmlPatch => {
+            return Err("an XML patch cannot add or remove a whole document".to_string());
+        }
+        _ => None,
+    };
+
+    let mut stdout = StandardStream::stdout(match args.color {
+        Color::Always => ColorChoice::Always,
+        Color::Auto if io::stdout().is_terminal() => ColorChoice::Auto,
+        Color::Auto | Color::Never => ColorChoice::Never,
+    });
+    let options = PrintTreeDiffOptions::default()
+        .with_namespace(args.namespace)
+        .with_diff_options(args.diff_options.clone());
+    let write = || {
+        if let Some(header) = &header {
+            writeln!(stdout, "{header}")?;
+        }
+        match (&tree1, &tree2, &diff) {
+            (_, _, Some(diff)) if args.format == Format::Edits => {
+                for edit in diff.iter() {
+                    writeln!(stdout, "{edit:#}")?;
+                }
+            }
+            (_, _, Some(diff)) if args.format == Format::Json => {
+                writeln!(stdout, "{}", to_json(diff))?
+            }
+            (_, _, Some(diff)) if args.format == Format::XmlPatch => {
+                writeln!(stdout, "{}", to_xml_patch(diff))?
+            }
+            (Some(tree1), Some(tree2), _) => write_tree_diff(&mut stdout, tree1, tree2, options)?,
+            (None, Some(_), _) if args.format == Format::Edits => {
+                writeln!(stdout, "insert document {path2}")?
+            }
+            (Some(_), None, _) if args.format == Format::Edits => {
+                writeln!(stdout, "delete document {path1}")?
+            }
+            (None, Some(tree2), _) if args.format == Format::Json => {
+                writeln!(stdout, "{}", insert_document_to_json(tree2))?
+            }
+            (Some(tree1), None, _) if args.format == Format::Json => {
+                writeln!(stdout, "{}", delete_document_to_json(tree1))?
+            }
+            (None, Some(tree2), _) => write_inserted_tree(&mut stdout, tree2, options)?,
+            (Some(tree1), None, _) => write_deleted_tree(&mut stdout, tree1, options)?,
+            (None, None, _) => unreachable!(),
+        }
+        stdout.flush()
+    };
+    output_error(write())?;
+    Ok(true)
+}
+
+/// Compare the XML files of the directories and print a report. Returns whether they are different.
+fn compare_dirs(dir1: &str, dir2: &str, args: &Args) -> Result<bool, String> {
+    let options = DirDiffOptions::default().with_diff_options(args.diff_options.clone());
+    let diff = diff_dirs_with_options(Path::new(dir1), Path::new(dir2), &options)
+        .map_err(|e| e.to_string())?;
+    output_error(writeln!(io::stdout(), "{diff}"))?;
+    Ok(!diff.is_same())
+}
+
+/// Write a node and its subtree in a canonical form, one node per line.
+fn canonical_lines(node: XNode, sorted: bool) -> Vec<String> {
+    let line = match node.name() {
+        XNodeName::TagName(name) => match name.namespace() {
+            Some(namespace) => format!("<{{{namespace}}}{}>", name.name()),
+            None => format!("<{}>", name.name()),
+        },
+        XNodeName::AttributeName(attribute) => match attribute.namespace() {
+            Some(namespace) => {
+                format!(
+                    "@{{{namespace}}}{}={:?}",
+                    attribute.name(),
+                    attribute.value()
+                )
+            }
+            None => format!("@{}={:?}", attribute.name(), attribute.value()),
+        },
+        XNodeName::Text => format!("{:?}", node.value().unwrap_or_default().trim()),
+    };
+    let mut children: Vec<_> = node
+        .children()
+        .into_iter()
+        .map(|child| canonical_lines(child, sorted))
+        .collect();
+    if sorted {
+        children.sort();
+    } else {
+        // attributes are never ordered
+        children.sort_by_key(|lines| !lines[0].starts_with('@'));
+    }
+    let mut lines = vec![line];
+    for child in children {
+        lines.extend(child.into_iter().map(|line| format!("  {line}")));
+    }
+    lines
+}
+
+/// Write the file in a canonical form.
+fn textconv(path: &str, args: &Args) -> Result<(), String> {
+    let text = read(path)?;
+    let Some(tree) = parse(&text, path)? else {
+        return Ok(());
+    };
+    let mut stdout = io::stdout().lock();
+    let mut write = || {
+        for line in canonical_lines(tree.root(), !args.ordered) {
+            writeln!(stdout, "{line}")?;
+        }
+        stdout.flush()
+    };
+    output_error(write())
+}
+
+/// Run the command. Returns whether the files are different.
+fn run(args: &Args) -> Result<bool, String> {
+    let files = &args.files;
+    match args.mode {
+        Mode::Diff if Path::new(&files[0]).is_dir() && Path::new(&files[1]).is_dir() => {
+            compare_dirs(&files[0], &files[1], args)
+        }
+        Mode::Diff => compare(&files[0], &files[1], None, args),
+        Mode::Git if files.len() == 1 => {
+            output_error(writeln!(io::stdout(), "Unmerged path {}", files[0])).map(|_| false)
+        }
+        // git stops on a failing external diff
+        Mode::Git => {
+            // the new path of a renamed or copied file follows the seven usual arguments
+            let new_path = files.get(7).unwrap_or(&files[0]);
+            let header = format!("xdiff a/{} b/{}", files[0], new_path);
+            compare(&files[1], &files[4], Some(header), args).map(|_| false)
+        }
+        Mode::Textconv => textconv(&files[0], args).map(|_| false),
+    }
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("xdiff: {e}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    match run(&args) {
+        Ok(false) => ExitCode::SUCCESS,
+        Ok(true) => ExitCode::from(1),
+        Err(e) => {
+            eprintln!("xdiff: {e}");
+            ExitCode::from(2)
+        }
+    }
+}
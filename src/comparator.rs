@@ -0,0 +1,321 @@
+use std::{borrow::Cow, fmt::Debug};
+
+/// Comparison of the values of attributes and texts, see
+/// [crate::diff::DiffOptions::with_value_comparator]. By default, values are equal when they are
+/// identical, with the texts trimmed.
+///
+/// The hashes of the subtrees are calculated from the canonical forms of the values. A value
+/// without canonical form, such as a number compared with a tolerance, does not contribute to the
+/// hashes, and the subtrees which contain it are always compared node by node.
+pub trait ValueComparator: Debug + Send + Sync {
+    /// Get a canonical form of a value, the same for all the values equal to it. `None` if there is
+    /// none because the equality is not transitive.
+    fn canonical<'v>(&self, value: &'v str) -> Option<Cow<'v, str>>;
+
+    /// Check whether two values are equal. By default, compare their canonical forms.
+    fn equals(&self, value1: &str, value2: &str) -> bool {
+        match (self.canonical(value1), self.canonical(value2)) {
+            (Some(canonical1), Some(canonical2)) => canonical1 == canonical2,
+            _ => value1 == value2,
+        }
+    }
+}
+
+/// Values are equal regardless of their case.
+#[derive(Debug, Clone, Default)]
+pub struct CaseInsensitive;
+
+impl ValueComparator for CaseInsensitive {
+    fn canonical<'v>(&self, value: &'v str) -> Option<Cow<'v, str>> {
+        Some(Cow::Owned(value.to_lowercase()))
+    }
+}
+
+/// Values are equal when they have the same words, whatever the whitespace between them.
+#[derive(Debug, Clone, Default)]
+pub struct CollapseWhitespace;
+
+impl ValueComparator for CollapseWhitespace {
+    fn canonical<'v>(&self, value: &'v str) -> Option<Cow<'v, str>> {
+        Some(Cow::Owned(
+            value.split_whitespace().collect::<Vec<_>>().join(" "),
+        ))
+    }
+}
+
+/// Numbers are equal when they differ by at most the absolute tolerance, or by at most the relative
+/// tolerance times the largest of their magnitudes, e.g. `1.0` and `1.00`. Other values are equal
+/// when they are identical. Both tolerances are 0 by default.
+#[derive(Debug, Clone, Default)]
+pub struct NumericTolerance {
+    absolute: f64,
+    relative: f64,
+}
+
+impl NumericTolerance {
+    /// Set the largest difference between two equal numbers, e.g. `1e-9`. A negative tolerance is
+    /// taken as its absolute value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the tolerance is NaN.
+    pub fn with_absolute(mut self, tolerance: f64) -> Self {
+        self.absolute = Self::check(tolerance);
+        self
+    }
+
+    /// Set the largest difference between two equal numbers, as a fraction of the largest of their
+    /// magnitudes, e.g. `0.01` for 1%. A negative tolerance is taken as its absolute value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the tolerance is NaN.
+    pub fn with_relative(mut self, tolerance: f64) -> Self {
+        self.relative = Self::check(tolerance);
+        self
+    }
+
+    fn check(tolerance: f64) -> f64 {
+        assert!(!tolerance.is_nan(), "NaN tolerance");
+        tolerance.abs()
+    }
+
+    /// Parse a finite number.
+    pub(crate) fn parse(value: &str) -> Option<f64> {
+        let value = value.trim();
+        if !value.contains(|c: char| c.is_ascii_digit()) {
+            // e.g. `inf` or `NaN`
+            return None;
+        }
+        value.parse::<f64>().ok().filter(|n| n.is_finite())
+    }
+}
+
+impl ValueComparator for NumericTolerance {
+    fn canonical<'v>(&self, value: &'v str) -> Option<Cow<'v, str>> {
+        match Self::parse(value) {
+            Some(number) if self.absolute == 0.0 && self.relative == 0.0 => {
+                // `-0` is `0`
+                Some(Cow::Owned((number + 0.0).to_string()))
+            }
+            Some(_) => None,
+            None => Some(Cow::Borrowed(value)),
+        }
+    }
+
+    fn equals(&self, value1: &str, value2: &str) -> bool {
+        match (Self::parse(value1), Self::parse(value2)) {
+            (Some(number1), Some(number2)) => {
+                let difference = (number1 - number2).abs();
+                difference <= self.absolute
+                    || difference <= self.relative * number1.abs().max(number2.abs())
+            }
+            _ => value1 == value2,
+        }
+    }
+}
+
+/// Dates and times in the ISO 8601 format, such as `2024-05-01`, `2024-05-01T12:00:00Z` or
+/// `2024-05-01 14:00:00.50+02:00`, are equal when they stand for the same instant: times with a
+/// time zone offset are converted to UTC, and the optional seconds and their trailing zeros are
+/// normalized. Other values, including invalid dates such as `2024-02-31`, are equal when they are
+/// identical.
+#[derive(Debug, Clone, Default)]
+pub struct DateTime;
+
+impl ValueComparator for DateTime {
+    fn canonical<'v>(&self, value: &'v str) -> Option<Cow<'v, str>> {
+        Some(match normalize_date_time(value.trim()) {
+            Some(normalized) => Cow::Owned(normalized),
+            None => Cow::Borrowed(value),
+        })
+    }
+}
+
+/// Parse a fixed number of digits at the start of `s`.
+fn digits(s: &mut &str, count: usize) -> Option<i64> {
+    if s.len() < count || !s.as_bytes()[..count].iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    let (number, rest) = s.split_at(count);
+    *s = rest;
+    number.parse().ok()
+}
+
+fn separator(s: &mut &str, separators: &[char]) -> bool {
+    match s.strip_prefix(separators) {
+        Some(rest) => {
+            *s = rest;
+            true
+        }
+        None => false,
+    }
+}
+
+fn normalize_date_time(value: &str) -> Option<String> {
+    let mut s = value;
+    let year = digits(&mut s, 4)?;
+    separator(&mut s, &['-']).then_some(())?;
+    let month = digits(&mut s, 2).filter(|m| (1..=12).contains(m))?;
+    separator(&mut s, &['-']).then_some(())?;
+    let day = digits(&mut s, 2).filter(|d| (1..=days_in_month(year, month)).contains(d))?;
+    if s.is_empty() {
+        return Some(format!("{year:04}-{month:02}-{day:02}"));
+    }
+
+    separator(&mut s, &['T', 't', ' ']).then_some(())?;
+    let hour = digits(&mut s, 2).filter(|h| *h < 24)?;
+    separator(&mut s, &[':']).then_some(())?;
+    let minute = digits(&mut s, 2).filter(|m| *m < 60)?;
+    let mut second = 0;
+    let mut fraction = "";
+    if separator(&mut s, &[':']) {
+        second = digits(&mut s, 2).filter(|s| *s <= 60)?;
+        if separator(&mut s, &['.', ',']) {
+            let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+            (end > 0).then_some(())?;
+            fraction = s[..end].trim_end_matches('0');
+            s = &s[end..];
+        }
+    }
+    let fraction = if fraction.is_empty() {
+        String::new()
+    } else {
+        format!(".{fraction}")
+    };
+
+    let offset = if s.is_empty() {
+        None
+    } else if separator(&mut s, &['Z', 'z']) {
+        Some(0)
+    } else {
+        let sign = if separator(&mut s, &['+']) {
+            1
+        } else if separator(&mut s, &['-']) {
+            -1
+        } else {
+            return None;
+        };
+        let hours = digits(&mut s, 2)?;
+        separator(&mut s, &[':']);
+        let minutes = if s.is_empty() { 0 } else { digits(&mut s, 2)? };
+        Some(sign * (hours * 60 + minutes))
+    };
+    if !s.is_empty() {
+        return None;
+    }
+
+    let Some(offset) = offset else {
+        // a local time stays local
+        return Some(format!(
+            "{year:04}-{month:02}-{day:02}T{hour:02}:{minute:02}:{second:02}{fraction}"
+        ));
+    };
+    let minutes = days_from_civil(year, month, day) * 24 * 60 + hour * 60 + minute - offset;
+    let (year, month, day) = civil_from_days(minutes.div_euclid(24 * 60));
+    let minutes = minutes.rem_euclid(24 * 60);
+    Some(format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{second:02}{fraction}Z",
+        minutes / 60,
+        minutes % 60
+    ))
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Number of days since 1970-01-01 of a date of the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Date of the proleptic Gregorian calendar of a number of days since 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_comparators() {
+        assert!(CaseInsensitive.equals("Seattle", "SEATTLE"));
+        assert!(!CaseInsensitive.equals("Seattle", "Tacoma"));
+        assert!(CollapseWhitespace.equals("a  b\n c", "a b c"));
+        assert!(!CollapseWhitespace.equals("a b", "ab"));
+
+        let exact = NumericTolerance::default();
+        assert!(exact.equals("1.0", "1.00"));
+        assert_eq!(exact.canonical("1.0"), exact.canonical("1"));
+        assert!(!exact.equals("1.0", "1.01"));
+        assert!(exact.equals("abc", "abc"));
+        let tolerance = NumericTolerance::default().with_absolute(0.05);
+        assert!(tolerance.equals("1.0", "1.04"));
+        assert!(!tolerance.equals("1.0", "1.06"));
+        assert_eq!(tolerance.canonical("1.0"), None);
+        assert_eq!(tolerance.canonical("NaN").as_deref(), Some("NaN"));
+        let tolerance = NumericTolerance::default().with_relative(1e-6);
+        assert!(tolerance.equals("1000000", "1000000.5"));
+        assert!(!tolerance.equals("1", "1.5"));
+        let tolerance = NumericTolerance::default().with_absolute(-0.05);
+        assert!(tolerance.equals("1.0", "1.04"));
+        assert!(!tolerance.equals("1.0", "1.06"));
+
+        assert!(DateTime.equals("2024-05-01T12:00:00Z", "2024-05-01 14:00:00.000+02:00"));
+        assert!(DateTime.equals("2024-03-01T00:30+01:00", "2024-02-29T23:30:00Z"));
+        assert!(DateTime.equals("2023-12-31T23:00:00-01:00", "2024-01-01T00:00:00z"));
+        assert!(DateTime.equals("2024-05-01T12:00", "2024-05-01T12:00:00"));
+        assert!(!DateTime.equals("2024-05-01T12:00:00", "2024-05-01T12:00:00Z"));
+        assert!(!DateTime.equals("2024-05-01", "2024-05-02"));
+        assert!(DateTime.equals("tomorrow", "tomorrow"));
+        // days past the end of the month are not dates
+        assert_eq!(
+            DateTime.canonical("2024-02-31").as_deref(),
+            Some("2024-02-31")
+        );
+        assert!(!DateTime.equals("2024-02-31T00:00:00Z", "2024-03-02T00:00:00Z"));
+        assert!(!DateTime.equals("2023-02-29", "2023-03-01"));
+        assert!(!DateTime.equals("2024-04-31T12:00Z", "2024-05-01T12:00Z"));
+        assert!(DateTime.equals("2024-02-29T12:00Z", "2024-02-29T13:00+01:00"));
+        assert!(DateTime.equals("2000-02-29T00:00Z", "2000-02-28T23:00-01:00"));
+        assert!(!DateTime.equals("1900-02-29T00:00Z", "1900-03-01T00:00Z"));
+        assert_eq!(
+            DateTime
+                .canonical("2024-05-01T12:00:00.250+0530")
+                .as_deref(),
+            Some("2024-05-01T06:30:00.25Z")
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "NaN tolerance")]
+    fn test_nan_tolerance() {
+        let _ = NumericTolerance::default().with_relative(f64::NAN);
+    }
+}
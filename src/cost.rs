@@ -0,0 +1,135 @@
+use std::fmt::Debug;
+
+use crate::tree::XNode;
+
+/// Cost of the edit operations. The diff engine pairs up children so that the total cost of the
+/// edit script is minimal, so the cost model decides which changes are considered "small".
+pub trait CostModel: Debug + Send + Sync {
+    /// Cost of inserting `node` together with its whole subtree.
+    fn insert(&self, node: XNode<'_, '_>) -> u64;
+
+    /// Cost of deleting `node` together with its whole subtree.
+    fn delete(&self, node: XNode<'_, '_>) -> u64;
+
+    /// Cost of updating the value of a leaf (attribute or text) node.
+    fn update(&self, old: XNode<'_, '_>, new: XNode<'_, '_>) -> u64;
+
+    /// Cost of moving `node` to another position among its siblings, when children are compared in order.
+    fn reorder(&self, _node: XNode<'_, '_>) -> u64 {
+        1
+    }
+}
+
+/// Every node is weighted by its kind. Inserting or deleting a subtree costs the sum of the weights
+/// of its nodes, updating a leaf costs the weight of the leaf.
+///
+/// The default weights are all 1, so that the cost of a subtree is its size, as in the X-Diff paper.
+/// Costs saturate at `u64::MAX` rather than overflow with large weights.
+#[derive(Debug, Clone)]
+pub struct WeightedCost {
+    element: u64,
+    attribute: u64,
+    text: u64,
+}
+
+/// Every edit operation costs 1, regardless of the size of the subtree it touches.
+#[derive(Debug, Clone, Default)]
+pub struct UnitCost;
+
+impl Default for WeightedCost {
+    fn default() -> Self {
+        Self {
+            element: 1,
+            attribute: 1,
+            text: 1,
+        }
+    }
+}
+
+impl WeightedCost {
+    /// Set the weight of an element node, not counting its attributes and children. Default is 1.
+    pub fn with_element_weight(mut self, weight: u64) -> Self {
+        self.element = weight;
+        self
+    }
+
+    /// Set the weight of an attribute node. Default is 1.
+    pub fn with_attribute_weight(mut self, weight: u64) -> Self {
+        self.attribute = weight;
+        self
+    }
+
+    /// Set the weight of a text node. Default is 1.
+    pub fn with_text_weight(mut self, weight: u64) -> Self {
+        self.text = weight;
+        self
+    }
+
+    fn weight(&self, node: XNode) -> u64 {
+        if node.is_attribute() {
+            self.attribute
+        } else if node.is_text() {
+            self.text
+        } else {
+            self.element
+        }
+    }
+
+    fn subtree_weight(&self, node: XNode) -> u64 {
+        node.children()
+            .into_iter()
+            .fold(self.weight(node), |weight, child| {
+                weight.saturating_add(self.subtree_weight(child))
+            })
+    }
+}
+
+impl CostModel for WeightedCost {
+    fn insert(&self, node: XNode<'_, '_>) -> u64 {
+        self.subtree_weight(node)
+    }
+
+    fn delete(&self, node: XNode<'_, '_>) -> u64 {
+        self.subtree_weight(node)
+    }
+
+    fn update(&self, old: XNode<'_, '_>, _new: XNode<'_, '_>) -> u64 {
+        self.weight(old)
+    }
+}
+
+impl CostModel for UnitCost {
+    fn insert(&self, _node: XNode<'_, '_>) -> u64 {
+        1
+    }
+
+    fn delete(&self, _node: XNode<'_, '_>) -> u64 {
+        1
+    }
+
+    fn update(&self, _old: XNode<'_, '_>, _new: XNode<'_, '_>) -> u64 {
+        1
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tree::XTree;
+
+    #[test]
+    fn test_weighted_cost() {
+        let tree = XTree::parse(r#"<a x="1"><b>text</b></a>"#).unwrap();
+        let root = tree.root();
+        assert_eq!(WeightedCost::default().delete(root), 4);
+        let cost = WeightedCost::default()
+            .with_element_weight(10)
+            .with_attribute_weight(0);
+        assert_eq!(cost.insert(root), 21);
+        let attr = root.children()[1];
+        assert!(attr.is_attribute());
+        assert_eq!(cost.update(attr, attr), 0);
+        let cost = WeightedCost::default().with_element_weight(u64::MAX);
+        assert_eq!(cost.insert(root), u64::MAX);
+    }
+}
@@ -1,13 +1,25 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{HashMap, HashSet, VecDeque},
     fmt::Display,
+    ops::Deref,
+    sync::Arc,
 };
 
-use crate::tree::{XNode, XTree};
+use crate::{
+    comparator::{NumericTolerance, ValueComparator},
+    cost::{CostModel, WeightedCost},
+    matching::min_cost_matching,
+    selector::Selector,
+    tree::{XNode, XNodeName, XTree},
+};
 use md5::Digest;
 
 trait Concat {
+    /// Combine two hashes, regardless of their order.
     fn concat(self, other: Self) -> Self;
+
+    /// Combine two hashes, taking their order into account.
+    fn chain(self, other: Self) -> Self;
 }
 
 impl Concat for Digest {
@@ -17,144 +29,1247 @@ impl Concat for Digest {
         }
         self
     }
+
+    fn chain(self, other: Self) -> Self {
+        md5::compute([self.0, other.0].concat())
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum Edit<'a, 'tree1, 'tree2> {
+    /// `child_node` is inserted under `to_node`. `position` is the position of `child_node` among the
+    /// children of its parent in `tree2`, see [XNode::position].
     Insert {
         child_node: XNode<'a, 'tree2>,
         to_node: XNode<'a, 'tree1>,
+        position: usize,
     },
     Delete(XNode<'a, 'tree1>),
     Update {
         old: XNode<'a, 'tree1>,
         new: XNode<'a, 'tree2>,
     },
-    ReplaceRoot,
+    /// The root elements have different names. Unless the diff of the children below the roots is
+    /// requested, this is the only edit of the edit script.
+    ReplaceRoot {
+        old: XNode<'a, 'tree1>,
+        new: XNode<'a, 'tree2>,
+    },
+    /// An identical subtree changed position among its siblings. Only reported when children are
+    /// compared in order. `from` and `to` are the positions in the children of the parent node in
+    /// `tree1` and `tree2` respectively.
+    Reorder {
+        old: XNode<'a, 'tree1>,
+        new: XNode<'a, 'tree2>,
+        from: usize,
+        to: usize,
+    },
+    /// An identical subtree was deleted under `from_parent` and inserted under `to_parent`, at the
+    /// same `position` as an [Edit::Insert]. Only reported when move detection is enabled.
+    Move {
+        node: XNode<'a, 'tree1>,
+        from_parent: XNode<'a, 'tree1>,
+        to_parent: XNode<'a, 'tree1>,
+        position: usize,
+    },
+    /// An element was renamed while keeping most of its children. The edits of its children follow
+    /// this edit in the edit script. Only reported when rename detection is enabled.
+    Rename {
+        old: XNode<'a, 'tree1>,
+        new: XNode<'a, 'tree2>,
+    },
 }
 
+/// With the alternate flag `{:#}`, nodes are located by their [XNode::xpath] instead of their id.
 impl Display for Edit<'_, '_, '_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let alternate = f.alternate();
+        let location = |node: &XNode| {
+            if alternate {
+                node.xpath()
+            } else {
+                node.id().to_string()
+            }
+        };
         match self {
             Edit::Insert {
                 child_node,
                 to_node,
+                position,
             } => {
                 write!(
                     f,
-                    "insert node {} to node {}",
-                    child_node.id(),
-                    to_node.id()
+                    "insert node {} to node {} at position {}",
+                    location(child_node),
+                    location(to_node),
+                    position
                 )
             }
-            Edit::Delete(node) => write!(f, "delete node {}", node.id()),
+            Edit::Delete(node) => write!(f, "delete node {}", location(node)),
             Edit::Update { old, new } => write!(
                 f,
                 "update node {}: {:?} -> {:?}",
-                old.id(),
+                location(old),
                 old.value().unwrap().trim(),
                 new.value().unwrap().trim()
             ),
-            Edit::ReplaceRoot => write!(f, "replace root node"),
+            Edit::ReplaceRoot { old, new } => write!(
+                f,
+                "replace root node: {:?} -> {:?}",
+                old.signature(),
+                new.signature()
+            ),
+            Edit::Reorder { old, from, to, .. } => write!(
+                f,
+                "reorder node {} from position {} to position {}",
+                location(old),
+                from,
+                to
+            ),
+            Edit::Move {
+                node,
+                from_parent,
+                to_parent,
+                position,
+            } => write!(
+                f,
+                "move node {} from node {} to node {} at position {}",
+                location(node),
+                location(from_parent),
+                location(to_parent),
+                position
+            ),
+            Edit::Rename { old, new } => write!(
+                f,
+                "rename node {}: {:?} -> {:?}",
+                location(old),
+                old.signature(),
+                new.signature()
+            ),
         }
     }
 }
 
-type Diff<'a, 'tree1, 'tree2> = Vec<Edit<'a, 'tree1, 'tree2>>;
+/// An edit script to transform `tree1` to `tree2`, together with the pairs of nodes matched by the
+/// diff. It dereferences to the slice of its edits.
+#[derive(Debug, Clone)]
+pub struct Diff<'a, 'tree1, 'tree2> {
+    edits: Vec<Edit<'a, 'tree1, 'tree2>>,
+    /// Pairs of matched nodes whose subtrees differ, and pairs of moved subtrees, by id of the node
+    /// of `tree1`.
+    matches: HashMap<String, (XNode<'a, 'tree1>, XNode<'a, 'tree2>)>,
+}
+
+impl<'a, 'tree1, 'tree2> Diff<'a, 'tree1, 'tree2> {
+    fn new() -> Self {
+        Self {
+            edits: Vec::new(),
+            matches: HashMap::new(),
+        }
+    }
+
+    fn push(&mut self, edit: Edit<'a, 'tree1, 'tree2>) {
+        self.edits.push(edit);
+    }
+
+    fn push_match(&mut self, node1: XNode<'a, 'tree1>, node2: XNode<'a, 'tree2>) {
+        self.matches.insert(node1.id().to_string(), (node1, node2));
+    }
+
+    fn append(&mut self, other: &mut Self) {
+        self.edits.append(&mut other.edits);
+        self.matches.extend(other.matches.drain());
+    }
+
+    /// Get the edits.
+    pub fn edits(&self) -> &[Edit<'a, 'tree1, 'tree2>] {
+        &self.edits
+    }
+
+    /// Get the node of `tree2` matched with `node` of `tree1`. Only nodes whose subtree changed, and
+    /// moved nodes, are recorded.
+    pub fn matched(&self, node: XNode) -> Option<XNode<'a, 'tree2>> {
+        self.matches
+            .get(&node.id().to_string())
+            .map(|(_, node2)| *node2)
+    }
+
+    /// Invert the edit script, to transform `tree2` back to `tree1`.
+    pub fn invert(&self) -> Diff<'a, 'tree2, 'tree1> {
+        let matched = |node: XNode<'a, 'tree1>| self.matched(node).unwrap();
+        let edits = self
+            .edits
+            .iter()
+            .map(|edit| match *edit {
+                Edit::Insert { child_node, .. } => Edit::Delete(child_node),
+                Edit::Delete(node) => Edit::Insert {
+                    child_node: node,
+                    to_node: matched(node.parent().unwrap()),
+                    position: node.position(),
+                },
+                Edit::Update { old, new } => Edit::Update { old: new, new: old },
+                Edit::ReplaceRoot { old, new } => Edit::ReplaceRoot { old: new, new: old },
+                Edit::Reorder { old, new, from, to } => Edit::Reorder {
+                    old: new,
+                    new: old,
+                    from: to,
+                    to: from,
+                },
+                Edit::Move {
+                    node,
+                    from_parent,
+                    to_parent,
+                    ..
+                } => Edit::Move {
+                    node: matched(node),
+                    from_parent: matched(to_parent),
+                    to_parent: matched(from_parent),
+                    position: node.position(),
+                },
+                Edit::Rename { old, new } => Edit::Rename { old: new, new: old },
+            })
+            .collect();
+        Diff {
+            edits,
+            matches: self
+                .matches
+                .values()
+                .map(|(node1, node2)| (node2.id().to_string(), (*node2, *node1)))
+                .collect(),
+        }
+    }
+}
+
+impl<'a, 'tree1, 'tree2> Deref for Diff<'a, 'tree1, 'tree2> {
+    type Target = [Edit<'a, 'tree1, 'tree2>];
+
+    fn deref(&self) -> &Self::Target {
+        &self.edits
+    }
+}
+
+impl<'a, 'tree1, 'tree2> IntoIterator for Diff<'a, 'tree1, 'tree2> {
+    type Item = Edit<'a, 'tree1, 'tree2>;
+    type IntoIter = std::vec::IntoIter<Self::Item>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.edits.into_iter()
+    }
+}
+
+impl<'d, 'a, 'tree1, 'tree2> IntoIterator for &'d Diff<'a, 'tree1, 'tree2> {
+    type Item = &'d Edit<'a, 'tree1, 'tree2>;
+    type IntoIter = std::slice::Iter<'d, Edit<'a, 'tree1, 'tree2>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.edits.iter()
+    }
+}
+
+/// Strategy used to pair up the children which are not identical in both trees.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Matching {
+    /// Minimum-cost bipartite matching, as prescribed by the X-Diff paper.
+    #[default]
+    Optimal,
+    /// Pair children with the shortest edit scripts first. Faster, but may produce longer edit scripts.
+    Greedy,
+}
+
+/// How the children of an element are compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChildOrder {
+    /// The order of the children is irrelevant, as in the X-Diff paper.
+    #[default]
+    Unordered,
+    /// The children form a sequence. They are aligned in order, and children that only changed
+    /// position are reported as [Edit::Reorder]. Attributes are always compared unorderedly.
+    Ordered,
+}
+
+/// A field of the key identifying an element among its siblings, see [DiffOptions::with_key_for].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyField {
+    /// The value of the attribute with this local name.
+    Attribute(String),
+    /// The trimmed text of the first child element with this local name.
+    Child(String),
+}
+
+impl KeyField {
+    fn value(&self, node: XNode) -> Option<String> {
+        let children = node.children();
+        match self {
+            KeyField::Attribute(name) => children
+                .iter()
+                .find(|n| matches!(n.name(), XNodeName::AttributeName(a) if a.name() == name))
+                .and_then(|n| n.value())
+                .map(str::to_string),
+            KeyField::Child(name) => children
+                .iter()
+                .find(|n| matches!(n.name(), XNodeName::TagName(t) if t.name() == name))
+                .map(|n| {
+                    let text = n.children().into_iter().find(|c| c.is_text());
+                    text.map_or(String::new(), |t| {
+                        t.value().unwrap_or_default().trim().to_string()
+                    })
+                }),
+        }
+    }
+}
+
+/// Options to control the diff calculation.
+#[derive(Debug, Clone)]
+pub struct DiffOptions {
+    matching: Matching,
+    cost_model: Arc<dyn CostModel>,
+    order: ChildOrder,
+    order_rules: Vec<(Selector, ChildOrder)>,
+    ignore_rules: Vec<Selector>,
+    key_rules: Vec<(Selector, Vec<KeyField>)>,
+    comparator_rules: Vec<(Selector, Arc<dyn ValueComparator>)>,
+    numeric_tolerance: Option<NumericTolerance>,
+    detect_moves: bool,
+    rename_similarity: Option<f64>,
+    diff_replaced_root: bool,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            matching: Matching::default(),
+            cost_model: Arc::new(WeightedCost::default()),
+            order: ChildOrder::default(),
+            order_rules: Vec::new(),
+            ignore_rules: Vec::new(),
+            key_rules: Vec::new(),
+            comparator_rules: Vec::new(),
+            numeric_tolerance: None,
+            detect_moves: false,
+            rename_similarity: None,
+            diff_replaced_root: false,
+        }
+    }
+}
+
+impl DiffOptions {
+    /// Set the strategy used to pair up children. Default is [Matching::Optimal].
+    pub fn with_matching(mut self, matching: Matching) -> Self {
+        self.matching = matching;
+        self
+    }
+
+    /// Set the cost model used to rank the candidate pairs of children. Default is [WeightedCost::default],
+    /// which makes the cost of a subtree equal its size.
+    pub fn with_cost_model(mut self, cost_model: impl CostModel + 'static) -> Self {
+        self.cost_model = Arc::new(cost_model);
+        self
+    }
+
+    /// Set how the children of elements are compared. Default is [ChildOrder::Unordered].
+    pub fn with_child_order(mut self, order: ChildOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Set how the children of the selected elements are compared, overriding [Self::with_child_order].
+    /// When several rules select the same element, the first one added wins.
+    pub fn with_child_order_for(mut self, selector: Selector, order: ChildOrder) -> Self {
+        self.order_rules.push((selector, order));
+        self
+    }
+
+    /// Ignore the selected nodes and their subtrees, such as timestamps or generated ids: they never
+    /// contribute to the hashes or to the edits. Several rules can be added. The root element is
+    /// never ignored.
+    pub fn with_ignored(mut self, selector: Selector) -> Self {
+        self.ignore_rules.push(selector);
+        self
+    }
+
+    /// Identify the selected elements among their siblings by the values of key fields, such as an
+    /// `id` attribute. The elements with a key are only matched with the elements with the same name
+    /// and key, so that a changed element is updated rather than deleted and inserted again. When
+    /// several rules select the same element, the first one added wins.
+    pub fn with_key_for(mut self, selector: Selector, key: Vec<KeyField>) -> Self {
+        self.key_rules.push((selector, key));
+        self
+    }
+
+    /// Compare the values of the selected attributes and texts with a [ValueComparator], such as
+    /// [crate::comparator::CaseInsensitive], instead of requiring identical values. A rule selecting
+    /// an element applies to its texts. When several rules select the same value, the first one
+    /// added wins.
+    pub fn with_value_comparator(
+        mut self,
+        selector: Selector,
+        comparator: impl ValueComparator + 'static,
+    ) -> Self {
+        self.comparator_rules.push((selector, Arc::new(comparator)));
+        self
+    }
+
+    /// Compare the numbers in all the attributes and texts with a tolerance, such as
+    /// `NumericTolerance::default().with_relative(1e-9)`, so that `1.0` and `1.0000000001` are equal.
+    /// The values selected by [Self::with_value_comparator] keep their comparator. Disabled by
+    /// default.
+    pub fn with_numeric_tolerance(mut self, tolerance: NumericTolerance) -> Self {
+        self.numeric_tolerance = Some(tolerance);
+        self
+    }
+
+    /// Report an identical subtree that was deleted under one parent and inserted under another one
+    /// as a single [Edit::Move], instead of an [Edit::Delete] and an [Edit::Insert]. Default is `false`.
+    pub fn with_move_detection(mut self, yes: bool) -> Self {
+        self.detect_moves = yes;
+        self
+    }
+
+    /// Report a deleted and an inserted element under the same parent as a single [Edit::Rename] when
+    /// at least `similarity` (between 0 and 1) of their children are identical. Rename detection is
+    /// disabled by default.
+    pub fn with_rename_detection(mut self, similarity: f64) -> Self {
+        self.rename_similarity = Some(similarity);
+        self
+    }
+
+    /// When the root elements have different names, still calculate the diff of their children after
+    /// the [Edit::ReplaceRoot]. Default is `false`.
+    pub fn with_replaced_root_diff(mut self, yes: bool) -> Self {
+        self.diff_replaced_root = yes;
+        self
+    }
+
+    fn child_order(&self, node: XNode) -> ChildOrder {
+        self.order_rules
+            .iter()
+            .find(|(selector, _)| selector.matches(node))
+            .map_or(self.order, |(_, order)| *order)
+    }
+
+    /// Get the key of an element, `None` if it is not selected by a key rule or has no key field.
+    fn key(&self, node: XNode) -> Option<Vec<Option<String>>> {
+        let (_, fields) = self
+            .key_rules
+            .iter()
+            .find(|(selector, _)| selector.matches(node))?;
+        let key: Vec<_> = fields.iter().map(|field| field.value(node)).collect();
+        key.iter().any(Option::is_some).then_some(key)
+    }
+
+    /// Get the comparator of an attribute or text, `None` if it is not selected by a comparator rule
+    /// and there is no numeric tolerance.
+    fn comparator(&self, node: XNode) -> Option<&dyn ValueComparator> {
+        if node.is_element() {
+            return None;
+        }
+        let parent = node.parent().filter(|_| node.is_text());
+        self.comparator_rules
+            .iter()
+            .find(|(selector, _)| {
+                selector.matches(node) || parent.is_some_and(|p| selector.matches(p))
+            })
+            .map(|(_, comparator)| comparator.as_ref())
+            .or_else(|| {
+                let tolerance = self.numeric_tolerance.as_ref()?;
+                Some(tolerance as &dyn ValueComparator)
+            })
+    }
+}
 
 /// Calculate the difference between two XML trees, represented by the minum edit operations to transform `tree1` to `tree2`.
 pub fn diff<'a, 'doc1, 'doc2>(
     tree1: &'doc1 XTree<'doc1>,
     tree2: &'doc2 XTree<'doc2>,
 ) -> Diff<'a, 'doc1, 'doc2> {
+    diff_with_options(tree1, tree2, DiffOptions::default())
+}
+
+/// Same as [diff], but with options to control the calculation.
+pub fn diff_with_options<'a, 'doc1, 'doc2>(
+    tree1: &'doc1 XTree<'doc1>,
+    tree2: &'doc2 XTree<'doc2>,
+    options: DiffOptions,
+) -> Diff<'a, 'doc1, 'doc2> {
+    let (root1, root2) = (tree1.root(), tree2.root());
+    if root1.signature() != root2.signature() && !options.diff_replaced_root {
+        let mut diff = Diff::new();
+        diff.push(Edit::ReplaceRoot {
+            old: root1,
+            new: root2,
+        });
+        return diff;
+    }
+    let (ht1, fuzzy1) = calculate_hash_table(tree1, &options);
+    let (ht2, fuzzy2) = calculate_hash_table(tree2, &options);
+    let differ = Differ {
+        ht1,
+        ht2,
+        fuzzy1,
+        fuzzy2,
+        options: &options,
+    };
+    let mut diff = if root1.signature() != root2.signature() {
+        let mut diff = Diff::new();
+        diff.push(Edit::ReplaceRoot {
+            old: root1,
+            new: root2,
+        });
+        diff.push_match(root1, root2);
+        diff.append(&mut differ.diff_children(root1, root2));
+        diff
+    } else {
+        differ.diff_node(root1, root2)
+    };
+    if let Some(similarity) = options.rename_similarity {
+        diff = differ.detect_renames(diff, similarity);
+    }
+    if options.detect_moves {
+        diff = differ.detect_moves(diff);
+    }
+    diff
+}
+
+struct Differ<'o> {
+    ht1: HashMap<String, Digest>,
+    ht2: HashMap<String, Digest>,
+    /// Ids of the nodes of `tree1` whose subtree has a value without canonical form, see
+    /// [ValueComparator::canonical].
+    fuzzy1: HashSet<String>,
+    fuzzy2: HashSet<String>,
+    options: &'o DiffOptions,
+}
+
+impl Differ<'_> {
+    fn hash1(&self, node: XNode) -> Digest {
+        *self.ht1.get(&node.id().to_string()).unwrap()
+    }
+
+    fn hash2(&self, node: XNode) -> Digest {
+        *self.ht2.get(&node.id().to_string()).unwrap()
+    }
+
+    /// Check whether the hashes of two nodes prove that their subtrees are identical.
+    fn identical(&self, node1: XNode, node2: XNode) -> bool {
+        self.hash1(node1) == self.hash2(node2)
+            && !self.fuzzy1.contains(&node1.id().to_string())
+            && !self.fuzzy2.contains(&node2.id().to_string())
+    }
+
+    /// Check whether the subtrees of two nodes are equal. Unlike [Self::identical], the values
+    /// without canonical form, which share a hash, are compared with their comparator.
+    fn equal(&self, node1: XNode, node2: XNode) -> bool {
+        let (id1, id2) = (node1.id().to_string(), node2.id().to_string());
+        if self.ht1[&id1] != self.ht2[&id2] {
+            return false;
+        }
+        if !self.fuzzy1.contains(&id1) && !self.fuzzy2.contains(&id2) {
+            return true;
+        }
+        self.compare_values(node1, node2)
+    }
+
+    /// Compare the values of the subtrees of two nodes with the same hash, see [Self::equal], without
+    /// building their diff.
+    fn compare_values(&self, node1: XNode, node2: XNode) -> bool {
+        if !node1.is_element() {
+            return self.options.comparator(node1).is_some_and(|comparator| {
+                comparator.equals(
+                    node1.compared_value().unwrap_or_default(),
+                    node2.compared_value().unwrap_or_default(),
+                )
+            });
+        }
+        let children1 = self.children1(node1);
+        let mut children2 = self.children2(node2);
+        if children1.len() != children2.len() {
+            return false;
+        }
+        let ordered = self.options.child_order(node1) == ChildOrder::Ordered;
+        let (ordered1, unordered1): (Vec<_>, Vec<_>) = children1
+            .into_iter()
+            .partition(|n| ordered && !n.is_attribute());
+        let ordered2: Vec<_> = children2
+            .extract_if(.., |n| ordered && !n.is_attribute())
+            .collect();
+        ordered1.len() == ordered2.len()
+            && ordered1
+                .iter()
+                .zip(&ordered2)
+                .all(|(n1, n2)| self.equal(*n1, *n2))
+            // the other children are paired up in turn with the first equal one
+            && unordered1.into_iter().all(|n1| {
+                match children2.iter().position(|n2| self.equal(n1, *n2)) {
+                    Some(j) => {
+                        children2.swap_remove(j);
+                        true
+                    }
+                    None => false,
+                }
+            })
+    }
+
+    /// Get the children of a node of `tree1` which are not ignored, that is in the hash table.
+    fn children1<'a, 'doc>(&self, node: XNode<'a, 'doc>) -> Vec<XNode<'a, 'doc>> {
+        let children = node.children().into_iter();
+        children
+            .filter(|n| self.ht1.contains_key(&n.id().to_string()))
+            .collect()
+    }
+
+    /// Get the children of a node of `tree2` which are not ignored, that is in the hash table.
+    fn children2<'a, 'doc>(&self, node: XNode<'a, 'doc>) -> Vec<XNode<'a, 'doc>> {
+        let children = node.children().into_iter();
+        children
+            .filter(|n| self.ht2.contains_key(&n.id().to_string()))
+            .collect()
+    }
+
+    /// Pair up the deletions and insertions of identical subtrees into moves.
+    fn detect_moves<'a, 'doc1, 'doc2>(
+        &self,
+        diff: Diff<'a, 'doc1, 'doc2>,
+    ) -> Diff<'a, 'doc1, 'doc2> {
+        let mut inserted: Vec<_> = diff
+            .edits
+            .iter()
+            .map(|e| match e {
+                Edit::Insert { child_node, .. } => Some(self.hash2(*child_node)),
+                _ => None,
+            })
+            .collect();
+        let mut moves = HashMap::new();
+        for (i, e) in diff.edits.iter().enumerate() {
+            if let Edit::Delete(node) = e {
+                let hash = self.hash1(*node);
+                let identical = |j: usize| {
+                    let Edit::Insert { child_node, .. } = diff[j] else {
+                        unreachable!()
+                    };
+                    self.equal(*node, child_node)
+                };
+                if let Some(j) =
+                    (0..inserted.len()).find(|j| inserted[*j] == Some(hash) && identical(*j))
+                {
+                    inserted[j] = None;
+                    moves.insert(i, j);
+                }
+            }
+        }
+        let moved_to: HashMap<_, _> = moves.iter().map(|(i, j)| (*j, *i)).collect();
+        let mut matches = diff.matches.clone();
+        let edits = diff
+            .edits
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| !moved_to.contains_key(j))
+            .map(|(i, e)| match (e, moves.get(&i)) {
+                (Edit::Delete(node), Some(j)) => {
+                    let Edit::Insert {
+                        child_node,
+                        to_node,
+                        position,
+                    } = diff[*j]
+                    else {
+                        unreachable!()
+                    };
+                    matches.insert(node.id().to_string(), (*node, child_node));
+                    Edit::Move {
+                        node: *node,
+                        from_parent: node.parent().unwrap(),
+                        to_parent: to_node,
+                        position,
+                    }
+                }
+                _ => e.clone(),
+            })
+            .collect();
+        Diff { edits, matches }
+    }
+
+    /// Pair up the deletions and insertions of elements under the same parent whose children are
+    /// similar enough into renames, followed by the diff of their children.
+    fn detect_renames<'a, 'doc1, 'doc2>(
+        &self,
+        diff: Diff<'a, 'doc1, 'doc2>,
+        min_similarity: f64,
+    ) -> Diff<'a, 'doc1, 'doc2> {
+        let mut candidates = Vec::new();
+        for (i, e1) in diff.iter().enumerate() {
+            let Edit::Delete(old) = e1 else { continue };
+            if !old.is_element() {
+                continue;
+            }
+            for (j, e2) in diff.iter().enumerate() {
+                let Edit::Insert {
+                    child_node: new,
+                    to_node,
+                    ..
+                } = e2
+                else {
+                    continue;
+                };
+                if new.is_element() && old.parent() == Some(*to_node) {
+                    let similarity = self.similarity(*old, *new);
+                    if similarity >= min_similarity && similarity > 0.0 {
+                        candidates.push((similarity, i, j));
+                    }
+                }
+            }
+        }
+        candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+        let mut renames = HashMap::new();
+        let mut renamed_to = HashMap::new();
+        for (_, i, j) in candidates {
+            if !renames.contains_key(&i) && !renamed_to.contains_key(&j) {
+                renames.insert(i, j);
+                renamed_to.insert(j, i);
+            }
+        }
+
+        let mut result = Diff::new();
+        result.matches = diff.matches.clone();
+        for (i, e) in diff.iter().enumerate() {
+            if renamed_to.contains_key(&i) {
+                continue;
+            }
+            match (e, renames.get(&i)) {
+                (Edit::Delete(old), Some(j)) => {
+                    let Edit::Insert {
+                        child_node: new, ..
+                    } = diff[*j]
+                    else {
+                        unreachable!()
+                    };
+                    result.push(Edit::Rename { old: *old, new });
+                    result.push_match(*old, new);
+                    result.append(&mut self.diff_children(*old, new));
+                }
+                _ => result.push(e.clone()),
+            }
+        }
+        result
+    }
+
+    /// Dice coefficient of the multisets of children hashes.
+    fn similarity(&self, node1: XNode, node2: XNode) -> f64 {
+        let children1 = self.children1(node1);
+        let children2 = self.children2(node2);
+        if children1.is_empty() && children2.is_empty() {
+            return 0.0;
+        }
+        let mut hashes2: HashMap<Digest, usize> = HashMap::new();
+        for n in &children2 {
+            *hashes2.entry(self.hash2(*n)).or_default() += 1;
+        }
+        let mut common = 0;
+        for n in &children1 {
+            if let Some(count) = hashes2.get_mut(&self.hash1(*n))
+                && *count > 0
+            {
+                *count -= 1;
+                common += 1;
+            }
+        }
+        2.0 * common as f64 / (children1.len() + children2.len()) as f64
+    }
+
     fn diff_node<'a, 'doc1, 'doc2>(
+        &self,
         node1: XNode<'a, 'doc1>,
-        ht1: &HashMap<String, Digest>,
         node2: XNode<'a, 'doc2>,
-        ht2: &HashMap<String, Digest>,
     ) -> Diff<'a, 'doc1, 'doc2> {
-        if ht1.get(&node1.id().to_string()) == ht2.get(&node2.id().to_string()) {
-            return Vec::new();
+        let mut diff = Diff::new();
+        if self.identical(node1, node2) {
+            return diff;
         }
 
-        // Leaf nodes with different hashes mean different values
+        // Leaf nodes with different hashes mean different values, unless they have no canonical form
         if (node1.is_attribute() && node2.is_attribute()) || (node1.is_text() && node2.is_text()) {
-            return vec![Edit::Update {
-                old: node1,
-                new: node2,
-            }];
+            let equal = self.options.comparator(node1).is_some_and(|comparator| {
+                comparator.equals(
+                    node1.compared_value().unwrap_or_default(),
+                    node2.compared_value().unwrap_or_default(),
+                )
+            });
+            if !equal {
+                diff.push_match(node1, node2);
+                diff.push(Edit::Update {
+                    old: node1,
+                    new: node2,
+                });
+            }
+        } else {
+            let mut children_diff = self.diff_children(node1, node2);
+            if !children_diff.is_empty() {
+                diff.push_match(node1, node2);
+                diff.append(&mut children_diff);
+            }
         }
+        diff
+    }
 
-        let mut iht1: HashMap<_, _> = node1
-            .children()
-            .iter()
-            .map(|n| (*ht1.get(&n.id().to_string()).unwrap(), *n))
-            .collect();
-        let mut iht2: HashMap<_, _> = node2
-            .children()
-            .iter()
-            .map(|n| (*ht2.get(&n.id().to_string()).unwrap(), *n))
-            .collect();
-        let children_hashes1: HashSet<_> = iht1.keys().copied().collect();
-        let children_hashes2: HashSet<_> = iht2.keys().copied().collect();
-        let same_hashes: HashSet<_> = children_hashes1.intersection(&children_hashes2).collect();
-        iht1.retain(|k, _| !same_hashes.contains(&k));
-        iht2.retain(|k, _| !same_hashes.contains(&k));
-        let mut remaining_children1: HashSet<_> = iht1.into_values().collect();
-        let mut remaining_children2: HashSet<_> = iht2.into_values().collect();
+    fn diff_children<'a, 'doc1, 'doc2>(
+        &self,
+        node1: XNode<'a, 'doc1>,
+        node2: XNode<'a, 'doc2>,
+    ) -> Diff<'a, 'doc1, 'doc2> {
+        match self.options.child_order(node1) {
+            ChildOrder::Unordered => {
+                self.diff_unordered(node1, self.children1(node1), self.children2(node2))
+            }
+            ChildOrder::Ordered => {
+                // attributes are never ordered
+                let (attributes1, children1): (Vec<_>, Vec<_>) = self
+                    .children1(node1)
+                    .into_iter()
+                    .partition(|n| n.is_attribute());
+                let (attributes2, children2): (Vec<_>, Vec<_>) = self
+                    .children2(node2)
+                    .into_iter()
+                    .partition(|n| n.is_attribute());
+                let mut diff = self.diff_ordered(node1, children1, children2);
+                diff.append(&mut self.diff_unordered(node1, attributes1, attributes2));
+                diff
+            }
+        }
+    }
+
+    fn diff_unordered<'a, 'doc1, 'doc2>(
+        &self,
+        node1: XNode<'a, 'doc1>,
+        children1: Vec<XNode<'a, 'doc1>>,
+        children2: Vec<XNode<'a, 'doc2>>,
+    ) -> Diff<'a, 'doc1, 'doc2> {
+        let (mut keyed_diff, children1, children2) = self.diff_keyed(node1, children1, children2);
+
+        // Identical children are paired up as a multiset: a subtree that appears n times in `node1`
+        // and m times in `node2` is matched min(n, m) times, the surplus is left for the next step.
+        // Subtrees with values without canonical form are left for the next step.
+        let mut identical_hashes2: HashMap<Digest, usize> = HashMap::new();
+        for n in &children2 {
+            if !self.fuzzy2.contains(&n.id().to_string()) {
+                *identical_hashes2.entry(self.hash2(*n)).or_default() += 1;
+            }
+        }
+        let mut identical_hashes1: HashMap<Digest, usize> = HashMap::new();
+        let mut remaining_children1 = Vec::new();
+        for n in children1 {
+            let hash = self.hash1(n);
+            match identical_hashes2.get_mut(&hash) {
+                Some(count) if *count > 0 && !self.fuzzy1.contains(&n.id().to_string()) => {
+                    *count -= 1;
+                    *identical_hashes1.entry(hash).or_default() += 1;
+                }
+                _ => remaining_children1.push(n),
+            }
+        }
+        let mut remaining_children2 = Vec::new();
+        for n in children2 {
+            match identical_hashes1.get_mut(&self.hash2(n)) {
+                Some(count) if *count > 0 && !self.fuzzy2.contains(&n.id().to_string()) => {
+                    *count -= 1
+                }
+                _ => remaining_children2.push(n),
+            }
+        }
         let mut diff_pairs = Vec::new();
-        for n1 in &remaining_children1 {
-            for n2 in &remaining_children2 {
+        for (i, n1) in remaining_children1.iter().enumerate() {
+            for (j, n2) in remaining_children2.iter().enumerate() {
                 if n1.signature() == n2.signature() {
-                    diff_pairs.push((*n1, *n2, diff_node(*n1, ht1, *n2, ht2)));
+                    diff_pairs.push((i, j, self.diff_node(*n1, *n2)));
                 }
             }
         }
-        diff_pairs.sort_by_key(|item| item.2.len());
-        let mut diff = Vec::new();
-        for (n1, n2, mut d) in diff_pairs {
-            if remaining_children1.contains(&n1) && remaining_children2.contains(&n2) {
-                diff.append(&mut d);
-                remaining_children1.remove(&n1);
-                remaining_children2.remove(&n2);
+        let mut matched1 = vec![false; remaining_children1.len()];
+        let mut matched2 = vec![false; remaining_children2.len()];
+        let mut diff = Diff::new();
+        match self.options.matching {
+            Matching::Greedy => {
+                diff_pairs.sort_by_cached_key(|item| script_cost(&item.2, self.options));
+                for (i, j, mut d) in diff_pairs {
+                    if !matched1[i] && !matched2[j] {
+                        diff.append(&mut d);
+                        matched1[i] = true;
+                        matched2[j] = true;
+                    }
+                }
+            }
+            Matching::Optimal => {
+                let mut pair_costs =
+                    vec![vec![None; remaining_children2.len()]; remaining_children1.len()];
+                for (i, j, d) in &diff_pairs {
+                    pair_costs[*i][*j] = Some(script_cost(d, self.options));
+                }
+                let delete_costs: Vec<_> = remaining_children1
+                    .iter()
+                    .map(|n| self.options.cost_model.delete(*n))
+                    .collect();
+                let insert_costs: Vec<_> = remaining_children2
+                    .iter()
+                    .map(|n| self.options.cost_model.insert(*n))
+                    .collect();
+                let matching = min_cost_matching(&pair_costs, &delete_costs, &insert_costs);
+                for (i, j, mut d) in diff_pairs {
+                    if matching[i] == Some(j) {
+                        diff.append(&mut d);
+                        matched1[i] = true;
+                        matched2[j] = true;
+                    }
+                }
             }
         }
-        for n1 in remaining_children1 {
+        for (n1, _) in remaining_children1
+            .into_iter()
+            .zip(matched1)
+            .filter(|(_, m)| !m)
+        {
             diff.push(Edit::Delete(n1));
         }
-        for n2 in remaining_children2 {
+        for (n2, _) in remaining_children2
+            .into_iter()
+            .zip(matched2)
+            .filter(|(_, m)| !m)
+        {
+            diff.push(Edit::Insert {
+                child_node: n2,
+                to_node: node1,
+                position: n2.position(),
+            });
+        }
+        keyed_diff.append(&mut diff);
+        keyed_diff
+    }
+
+    /// Pair up the children with a key, see [DiffOptions::with_key_for], with the children with the
+    /// same name and key, in order. Returns their diff and the children without a key.
+    fn diff_keyed<'a, 'doc1, 'doc2>(
+        &self,
+        node1: XNode<'a, 'doc1>,
+        children1: Vec<XNode<'a, 'doc1>>,
+        children2: Vec<XNode<'a, 'doc2>>,
+    ) -> (
+        Diff<'a, 'doc1, 'doc2>,
+        Vec<XNode<'a, 'doc1>>,
+        Vec<XNode<'a, 'doc2>>,
+    ) {
+        let mut diff = Diff::new();
+        if self.options.key_rules.is_empty() {
+            return (diff, children1, children2);
+        }
+        let mut unkeyed2 = Vec::new();
+        let mut keyed2 = Vec::new();
+        let mut by_key2: HashMap<_, VecDeque<usize>> = HashMap::new();
+        for n in children2 {
+            match self.options.key(n) {
+                Some(key) => {
+                    by_key2
+                        .entry((n.signature().to_string(), key))
+                        .or_default()
+                        .push_back(keyed2.len());
+                    keyed2.push(Some(n));
+                }
+                None => unkeyed2.push(n),
+            }
+        }
+        let mut unkeyed1 = Vec::new();
+        for n1 in children1 {
+            let Some(key) = self.options.key(n1) else {
+                unkeyed1.push(n1);
+                continue;
+            };
+            let j = by_key2
+                .get_mut(&(n1.signature().to_string(), key))
+                .and_then(|indices| indices.pop_front());
+            match j.and_then(|j| keyed2[j].take()) {
+                Some(n2) => diff.append(&mut self.diff_node(n1, n2)),
+                None => diff.push(Edit::Delete(n1)),
+            }
+        }
+        for n2 in keyed2.into_iter().flatten() {
             diff.push(Edit::Insert {
                 child_node: n2,
                 to_node: node1,
+                position: n2.position(),
             });
         }
+        (diff, unkeyed1, unkeyed2)
+    }
+
+    /// Compare two sequences of children, keeping their order.
+    ///
+    /// Equal children are aligned first, using their longest common subsequence. Equal children
+    /// outside of this subsequence only changed position and are reported as
+    /// [Edit::Reorder]. The children between two aligned ones are then aligned with minimum cost.
+    fn diff_ordered<'a, 'doc1, 'doc2>(
+        &self,
+        node1: XNode<'a, 'doc1>,
+        children1: Vec<XNode<'a, 'doc1>>,
+        children2: Vec<XNode<'a, 'doc2>>,
+    ) -> Diff<'a, 'doc1, 'doc2> {
+        // the values are compared only for the children with the same hash
+        let hashes1: Vec<_> = children1.iter().map(|n| self.hash1(*n)).collect();
+        let hashes2: Vec<_> = children2.iter().map(|n| self.hash2(*n)).collect();
+        let equal: Vec<Vec<_>> = children1
+            .iter()
+            .zip(&hashes1)
+            .map(|(n1, h1)| {
+                children2
+                    .iter()
+                    .zip(&hashes2)
+                    .map(|(n2, h2)| h1 == h2 && self.equal(*n1, *n2))
+                    .collect()
+            })
+            .collect();
+        let (m, n) = (children1.len(), children2.len());
+
+        // lcs[i][j]: length of the longest common subsequence of children1[i..] and children2[j..]
+        let mut lcs = vec![vec![0usize; n + 1]; m + 1];
+        for i in (0..m).rev() {
+            for j in (0..n).rev() {
+                lcs[i][j] = if equal[i][j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+        let mut anchors = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < m && j < n {
+            if equal[i][j] {
+                anchors.push((i, j));
+                i += 1;
+                j += 1;
+            } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+
+        let mut used1 = vec![false; m];
+        let mut used2 = vec![false; n];
+        for (i, j) in &anchors {
+            used1[*i] = true;
+            used2[*j] = true;
+        }
+        let mut reordered = Diff::new();
+        for from in 0..m {
+            if used1[from] {
+                continue;
+            }
+            if let Some(to) = (0..n).find(|to| !used2[*to] && equal[from][*to]) {
+                used1[from] = true;
+                used2[to] = true;
+                reordered.push(Edit::Reorder {
+                    old: children1[from],
+                    new: children2[to],
+                    from,
+                    to,
+                });
+            }
+        }
+
+        let mut diff = Diff::new();
+        let (mut start1, mut start2) = (0, 0);
+        for (end1, end2) in anchors.into_iter().chain([(m, n)]) {
+            let segment1: Vec<_> = (start1..end1)
+                .filter(|i| !used1[*i])
+                .map(|i| children1[i])
+                .collect();
+            let segment2: Vec<_> = (start2..end2)
+                .filter(|j| !used2[*j])
+                .map(|j| children2[j])
+                .collect();
+            diff.append(&mut self.align(node1, segment1, segment2));
+            (start1, start2) = (end1 + 1, end2 + 1);
+        }
+        diff.append(&mut reordered);
         diff
     }
-    if tree1.root().signature() != tree2.root().signature() {
-        return vec![Edit::ReplaceRoot];
+
+    /// Align two sequences of children with minimum cost, keeping their order.
+    fn align<'a, 'doc1, 'doc2>(
+        &self,
+        node1: XNode<'a, 'doc1>,
+        children1: Vec<XNode<'a, 'doc1>>,
+        children2: Vec<XNode<'a, 'doc2>>,
+    ) -> Diff<'a, 'doc1, 'doc2> {
+        let (m, n) = (children1.len(), children2.len());
+        let mut pair_diffs = vec![vec![None; n]; m];
+        for (i, n1) in children1.iter().enumerate() {
+            for (j, n2) in children2.iter().enumerate() {
+                if n1.signature() == n2.signature()
+                    && self.options.key(*n1) == self.options.key(*n2)
+                {
+                    let d = self.diff_node(*n1, *n2);
+                    pair_diffs[i][j] = Some((script_cost(&d, self.options), d));
+                }
+            }
+        }
+        let delete_costs: Vec<_> = children1
+            .iter()
+            .map(|n| self.options.cost_model.delete(*n))
+            .collect();
+        let insert_costs: Vec<_> = children2
+            .iter()
+            .map(|n| self.options.cost_model.insert(*n))
+            .collect();
+
+        // costs[i][j]: minimum cost to transform children1[..i] into children2[..j]
+        let mut costs = vec![vec![0u64; n + 1]; m + 1];
+        for i in 1..=m {
+            costs[i][0] = costs[i - 1][0].saturating_add(delete_costs[i - 1]);
+        }
+        for j in 1..=n {
+            costs[0][j] = costs[0][j - 1].saturating_add(insert_costs[j - 1]);
+        }
+        for i in 1..=m {
+            for j in 1..=n {
+                let mut cost = (costs[i - 1][j].saturating_add(delete_costs[i - 1]))
+                    .min(costs[i][j - 1].saturating_add(insert_costs[j - 1]));
+                if let Some((c, _)) = &pair_diffs[i - 1][j - 1] {
+                    cost = cost.min(costs[i - 1][j - 1].saturating_add(*c));
+                }
+                costs[i][j] = cost;
+            }
+        }
+
+        let mut matched = Vec::new();
+        let mut deleted = Vec::new();
+        let mut inserted = Vec::new();
+        let (mut i, mut j) = (m, n);
+        while i > 0 || j > 0 {
+            if i > 0
+                && j > 0
+                && let Some((c, _)) = &pair_diffs[i - 1][j - 1]
+                && costs[i][j] == costs[i - 1][j - 1].saturating_add(*c)
+            {
+                matched.push((i - 1, j - 1));
+                i -= 1;
+                j -= 1;
+            } else if i > 0 && costs[i][j] == costs[i - 1][j].saturating_add(delete_costs[i - 1]) {
+                deleted.push(i - 1);
+                i -= 1;
+            } else {
+                inserted.push(j - 1);
+                j -= 1;
+            }
+        }
+        let mut diff = Diff::new();
+        for (i, j) in matched.into_iter().rev() {
+            diff.append(&mut pair_diffs[i][j].take().unwrap().1);
+        }
+        for i in deleted.into_iter().rev() {
+            diff.push(Edit::Delete(children1[i]));
+        }
+        for j in inserted.into_iter().rev() {
+            diff.push(Edit::Insert {
+                child_node: children2[j],
+                to_node: node1,
+                position: children2[j].position(),
+            });
+        }
+        diff
+    }
+}
+
+fn script_cost(diff: &Diff, options: &DiffOptions) -> u64 {
+    diff.iter()
+        .map(|edit| match edit {
+            Edit::Insert { child_node, .. } => options.cost_model.insert(*child_node),
+            Edit::Delete(node) => options.cost_model.delete(*node),
+            Edit::Update { old, new } => options.cost_model.update(*old, *new),
+            Edit::Reorder { old, .. } => options.cost_model.reorder(*old),
+            Edit::ReplaceRoot { .. } | Edit::Move { .. } | Edit::Rename { .. } => unreachable!(),
+        })
+        .fold(0, u64::saturating_add)
+}
+
+/// Get the ids of the nodes of a tree ignored by the options. The descendants of an ignored node may
+/// be missing.
+fn ignored_nodes(tree: &XTree, options: &DiffOptions) -> HashSet<String> {
+    let mut ignored = HashSet::new();
+    if options.ignore_rules.is_empty() {
+        return ignored;
+    }
+    // an XPath is evaluated once on the whole tree
+    for rule in &options.ignore_rules {
+        if let Selector::XPath(xpath) = rule {
+            ignored.extend(xpath.select(tree).iter().map(|n| n.id().to_string()));
+        }
     }
-    let ht1 = calculate_hash_table(tree1);
-    let ht2 = calculate_hash_table(tree2);
-    diff_node(tree1.root(), &ht1, tree2.root(), &ht2)
+    let mut nodes = tree.root().children();
+    while let Some(node) = nodes.pop() {
+        let id = node.id().to_string();
+        if ignored.contains(&id)
+            || options
+                .ignore_rules
+                .iter()
+                .any(|rule| !matches!(rule, Selector::XPath(_)) && rule.matches(node))
+        {
+            ignored.insert(id);
+        } else {
+            nodes.extend(node.children());
+        }
+    }
+    ignored
 }
 
-fn calculate_hash_table(tree: &XTree) -> HashMap<String, Digest> {
-    fn hash_of_node(node: XNode, ht: &mut HashMap<String, Digest>) -> Digest {
-        let hash = if node.children().is_empty() {
+/// Calculate the hashes of the nodes of a tree, by id, and the ids of the nodes whose subtree has a
+/// value without canonical form, see [ValueComparator::canonical]. The ignored nodes have no hash.
+fn calculate_hash_table(
+    tree: &XTree,
+    options: &DiffOptions,
+) -> (HashMap<String, Digest>, HashSet<String>) {
+    fn hash_of_node(
+        node: XNode,
+        ht: &mut HashMap<String, Digest>,
+        fuzzy: &mut HashSet<String>,
+        options: &DiffOptions,
+        ignored: &HashSet<String>,
+    ) -> Digest {
+        let children: Vec<_> = node
+            .children()
+            .into_iter()
+            .filter(|n| !ignored.contains(&n.id().to_string()))
+            .collect();
+        let hash = if let Some(comparator) = options.comparator(node) {
+            match comparator.canonical(node.compared_value().unwrap_or_default()) {
+                Some(canonical) => node.hash_with_value(&canonical),
+                None => {
+                    fuzzy.insert(node.id().to_string());
+                    node.hash_with_value("")
+                }
+            }
+        } else if children.is_empty() {
             node.hash()
         } else {
-            let mut acc = node.hash();
-            for child in node.children() {
-                acc = acc.concat(hash_of_node(child, ht));
+            let ordered = options.child_order(node) == ChildOrder::Ordered;
+            let mut acc = Digest([0; 16]);
+            for child in children {
+                let child_hash = hash_of_node(child, ht, fuzzy, options, ignored);
+                if fuzzy.contains(&child.id().to_string()) {
+                    fuzzy.insert(node.id().to_string());
+                }
+                acc = if ordered && !child.is_attribute() {
+                    acc.chain(child_hash)
+                } else {
+                    acc.concat(child_hash)
+                };
             }
-            acc
+            // chaining keeps the structure: a subtree moved to another parent changes both hashes
+            node.hash().chain(acc)
         };
         ht.insert(node.id().to_string(), hash);
         hash
     }
     let mut hash_table = HashMap::new();
-    hash_of_node(tree.root(), &mut hash_table);
-    hash_table
+    let mut fuzzy = HashSet::new();
+    let ignored = ignored_nodes(tree, options);
+    hash_of_node(tree.root(), &mut hash_table, &mut fuzzy, options, &ignored);
+    (hash_table, fuzzy)
 }
 
 #[cfg(test)]
@@ -163,17 +1278,22 @@ mod test {
     use crate::tree::print::{PrintTreeOptions, print_tree};
 
     use super::*;
+    use crate::{
+        comparator::{CaseInsensitive, CollapseWhitespace, DateTime, NumericTolerance},
+        cost::UnitCost,
+        xpath::XPath,
+    };
     use std::fs;
 
     #[test]
     fn test_calculate_hash_table_same_tree() {
         let text1 = fs::read_to_string("test/file1.xml").unwrap();
         let tree1 = XTree::parse(&text1).unwrap();
-        let ht1 = calculate_hash_table(&tree1);
+        let (ht1, _) = calculate_hash_table(&tree1, &DiffOptions::default());
 
         let text2 = fs::read_to_string("test/file1.xml").unwrap();
         let tree2 = XTree::parse(&text2).unwrap();
-        let ht2 = calculate_hash_table(&tree2);
+        let (ht2, _) = calculate_hash_table(&tree2, &DiffOptions::default());
 
         assert_eq!(
             ht1.get(&tree1.root().id().to_string()),
@@ -185,11 +1305,11 @@ mod test {
     fn test_calculate_hash_table_different_tree() {
         let text1 = fs::read_to_string("test/file1.xml").unwrap();
         let tree1 = XTree::parse(&text1).unwrap();
-        let ht1 = calculate_hash_table(&tree1);
+        let (ht1, _) = calculate_hash_table(&tree1, &DiffOptions::default());
 
         let text2 = fs::read_to_string("test/file2.xml").unwrap();
         let tree2 = XTree::parse(&text2).unwrap();
-        let ht2 = calculate_hash_table(&tree2);
+        let (ht2, _) = calculate_hash_table(&tree2, &DiffOptions::default());
 
         assert_ne!(
             ht1.get(&tree1.root().id().to_string()),
@@ -197,6 +1317,660 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_calculate_hash_table_structure() {
+        // `c` moved from under `b` to under `d`. Summing the hashes of a node and of all its
+        // descendants, as the hashes once were, gives both roots the same hash, so the move is
+        // never seen. Chaining the hash of a node with the sum of its children keeps the levels
+        // apart, while the moved subtree keeps its hash for move detection.
+        let tree1 = XTree::parse("<a><b><c/></b><d/></a>").unwrap();
+        let tree2 = XTree::parse("<a><b/><d><c/></d></a>").unwrap();
+        let (ht1, _) = calculate_hash_table(&tree1, &DiffOptions::default());
+        let (ht2, _) = calculate_hash_table(&tree2, &DiffOptions::default());
+        let hash = |ht: &HashMap<String, Digest>, node: XNode| ht[&node.id().to_string()];
+        let summed = |tree: &XTree| {
+            let mut nodes = vec![tree.root()];
+            let mut sum = Digest([0; 16]);
+            while let Some(node) = nodes.pop() {
+                sum = sum.concat(node.hash());
+                nodes.extend(node.children());
+            }
+            sum
+        };
+        assert_eq!(summed(&tree1), summed(&tree2));
+        assert_ne!(hash(&ht1, tree1.root()), hash(&ht2, tree2.root()));
+        let c1 = tree1.root().children()[0].children()[0];
+        let c2 = tree2.root().children()[1].children()[0];
+        assert_eq!(hash(&ht1, c1), hash(&ht2, c2));
+
+        let options = DiffOptions::default().with_move_detection(true);
+        let edits: Vec<_> = diff_with_options(&tree1, &tree2, options)
+            .iter()
+            .map(|edit| format!("{edit:#}"))
+            .collect();
+        assert_eq!(
+            edits,
+            ["move node /a/b/c from node /a/b to node /a/d at position 0"]
+        );
+    }
+
+    #[test]
+    fn test_diff_optimal_matching() {
+        let text1 = r#"<r><a f1="0" f2="0" f3="0" f4="0" f5="0"/><a f1="1" f2="0" f3="0" f4="1" f5="1"/></r>"#;
+        let text2 = r#"<r><a f1="1" f2="0" f3="0" f4="0" f5="0"/><a f1="0" f2="1" f3="1" f4="0" f5="0"/></r>"#;
+        let tree1 = XTree::parse(text1).unwrap();
+        let tree2 = XTree::parse(text2).unwrap();
+
+        let greedy = diff_with_options(
+            &tree1,
+            &tree2,
+            DiffOptions::default().with_matching(Matching::Greedy),
+        );
+        assert_eq!(greedy.len(), 6);
+
+        let optimal = diff(&tree1, &tree2);
+        assert_eq!(optimal.len(), 4);
+        assert!(optimal.iter().all(|e| matches!(e, Edit::Update { .. })));
+    }
+
+    #[test]
+    fn test_diff_cost_model() {
+        let text1 = r#"<r><a f1="1" f2="1" f3="1" f4="1" f5="1"/></r>"#;
+        let text2 = r#"<r><a f1="2" f2="2" f3="2" f4="2" f5="2"/></r>"#;
+        let tree1 = XTree::parse(text1).unwrap();
+        let tree2 = XTree::parse(text2).unwrap();
+
+        // Five attribute updates are cheaper than deleting and inserting a subtree of size 6.
+        let diff1 = diff(&tree1, &tree2);
+        assert_eq!(diff1.len(), 5);
+        assert!(diff1.iter().all(|e| matches!(e, Edit::Update { .. })));
+
+        // When every edit costs 1, replacing the element is cheaper.
+        let diff2 = diff_with_options(
+            &tree1,
+            &tree2,
+            DiffOptions::default().with_cost_model(UnitCost),
+        );
+        assert_eq!(diff2.len(), 2);
+        assert!(matches!(diff2[0], Edit::Delete(_)));
+        assert!(matches!(diff2[1], Edit::Insert { .. }));
+    }
+
+    #[test]
+    fn test_diff_cost_model_saturated() {
+        let tree1 = XTree::parse("<r><a><b/></a><c/><d/></r>").unwrap();
+        let tree2 = XTree::parse("<r><d/><a><b/><e/></a></r>").unwrap();
+        let cost = WeightedCost::default().with_element_weight(u64::MAX);
+        for order in [ChildOrder::Unordered, ChildOrder::Ordered] {
+            let options = DiffOptions::default()
+                .with_cost_model(cost.clone())
+                .with_child_order(order);
+            let diff = diff_with_options(&tree1, &tree2, options);
+            assert!(!diff.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_diff_duplicate_children_inserted() {
+        let text1 = "<List><Item>1</Item><Item>2</Item></List>";
+        let text2 = "<List><Item>1</Item><Item>1</Item><Item>2</Item><Item>1</Item></List>";
+        let tree1 = XTree::parse(text1).unwrap();
+        let tree2 = XTree::parse(text2).unwrap();
+        let diff = diff(&tree1, &tree2);
+        assert_eq!(diff.len(), 2);
+        assert!(diff.iter().all(|e| matches!(
+            e,
+            Edit::Insert { child_node, .. } if child_node.children()[0].value() == Some("1")
+        )));
+    }
+
+    #[test]
+    fn test_diff_duplicate_children_deleted() {
+        let text1 =
+            "<List><Item>1</Item><Item>1</Item><Item>2</Item><Item>2</Item><Item>2</Item></List>";
+        let text2 = "<List><Item>2</Item><Item>1</Item></List>";
+        let tree1 = XTree::parse(text1).unwrap();
+        let tree2 = XTree::parse(text2).unwrap();
+        let diff = diff(&tree1, &tree2);
+        let deleted: Vec<_> = diff
+            .iter()
+            .map(|e| match e {
+                Edit::Delete(node) => node.children()[0].value().unwrap().to_string(),
+                _ => panic!("unexpected edit {e}"),
+            })
+            .collect();
+        assert_eq!(deleted, vec!["1", "2", "2"]);
+    }
+
+    #[test]
+    fn test_diff_duplicate_children_updated() {
+        let text1 = "<List><Item>1</Item><Item>1</Item><Item>1</Item></List>";
+        let text2 = "<List><Item>1</Item><Item>3</Item><Item>1</Item></List>";
+        let tree1 = XTree::parse(text1).unwrap();
+        let tree2 = XTree::parse(text2).unwrap();
+        let diff = diff(&tree1, &tree2);
+        assert_eq!(diff.len(), 1);
+        assert!(matches!(
+            diff[0],
+            Edit::Update { old, new } if old.value() == Some("1") && new.value() == Some("3")
+        ));
+    }
+
+    #[test]
+    fn test_diff_ordered_reorder() {
+        let text1 = "<Steps><Step>a</Step><Step>b</Step><Step>c</Step></Steps>";
+        let text2 = "<Steps><Step>c</Step><Step>a</Step><Step>b</Step></Steps>";
+        let tree1 = XTree::parse(text1).unwrap();
+        let tree2 = XTree::parse(text2).unwrap();
+        assert!(diff(&tree1, &tree2).is_empty());
+
+        let diff = diff_with_options(
+            &tree1,
+            &tree2,
+            DiffOptions::default().with_child_order(ChildOrder::Ordered),
+        );
+        assert_eq!(diff.len(), 1);
+        assert!(matches!(
+            diff[0],
+            Edit::Reorder { from: 2, to: 0, old, .. } if old.children()[0].value() == Some("c")
+        ));
+    }
+
+    #[test]
+    fn test_diff_ordered_alignment() {
+        let text1 = r#"<p a="1">one <b>two</b> three <i>four</i></p>"#;
+        let text2 = r#"<p a="2">one <b>2</b> <i>four</i> five</p>"#;
+        let tree1 = XTree::parse(text1).unwrap();
+        let tree2 = XTree::parse(text2).unwrap();
+        let diff = diff_with_options(
+            &tree1,
+            &tree2,
+            DiffOptions::default().with_child_order(ChildOrder::Ordered),
+        )
+        .iter()
+        .map(|e| match e {
+            Edit::Update { old, new } => {
+                format!(
+                    "update {:?} -> {:?}",
+                    old.value().unwrap(),
+                    new.value().unwrap()
+                )
+            }
+            Edit::Delete(node) => format!("delete {:?}", node.value().unwrap()),
+            Edit::Insert { child_node, .. } => format!("insert {:?}", child_node.value().unwrap()),
+            _ => panic!("unexpected edit {e}"),
+        })
+        .collect::<Vec<_>>();
+        assert_eq!(
+            diff,
+            vec![
+                r#"update "two" -> "2""#,
+                r#"delete " three ""#,
+                r#"insert " five""#,
+                r#"update "1" -> "2""#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_child_order_per_element() {
+        let text1 = r#"
+<Customer>
+    <Name>George</Name>
+    <Phone>206</Phone>
+    <Steps><Step>a</Step><Step>b</Step></Steps>
+</Customer>"#;
+        let text2 = r#"
+<Customer>
+    <Phone>206</Phone>
+    <Name>George</Name>
+    <Steps><Step>b</Step><Step>a</Step></Steps>
+</Customer>"#;
+        let tree1 = XTree::parse(text1).unwrap();
+        let tree2 = XTree::parse(text2).unwrap();
+
+        let diff = diff_with_options(
+            &tree1,
+            &tree2,
+            DiffOptions::default()
+                .with_child_order_for(Selector::Name("Steps".to_string()), ChildOrder::Ordered),
+        );
+        assert_eq!(diff.len(), 1);
+        assert!(matches!(
+            diff[0],
+            Edit::Reorder { old, .. } if old.parent().unwrap().signature() == ":Steps"
+        ));
+
+        let diff = diff_with_options(
+            &tree1,
+            &tree2,
+            DiffOptions::default()
+                .with_child_order(ChildOrder::Ordered)
+                .with_child_order_for(
+                    Selector::Path("/Customer".to_string()),
+                    ChildOrder::Unordered,
+                ),
+        );
+        assert_eq!(diff.len(), 1);
+        assert!(matches!(
+            diff[0],
+            Edit::Reorder { old, .. } if old.parent().unwrap().signature() == ":Steps"
+        ));
+    }
+
+    #[test]
+    fn test_diff_move_detection() {
+        let text1 = r#"
+<Customers>
+    <Customer id="1"><Name>Ann</Name><Phone>111</Phone><Address>Seattle</Address></Customer>
+    <Customer id="2"><Name>Bob</Name><Phone>222</Phone></Customer>
+</Customers>"#;
+        let text2 = r#"
+<Customers>
+    <Customer id="1"><Name>Ann</Name><Phone>111</Phone></Customer>
+    <Customer id="2"><Name>Bob</Name><Phone>222</Phone><Address>Seattle</Address></Customer>
+</Customers>"#;
+        let tree1 = XTree::parse(text1).unwrap();
+        let tree2 = XTree::parse(text2).unwrap();
+
+        let diff1 = diff(&tree1, &tree2);
+        assert_eq!(diff1.len(), 2);
+
+        let diff2 = diff_with_options(
+            &tree1,
+            &tree2,
+            DiffOptions::default().with_move_detection(true),
+        );
+        assert_eq!(diff2.len(), 1);
+        let Edit::Move {
+            node,
+            from_parent,
+            to_parent,
+            position,
+        } = diff2[0]
+        else {
+            panic!("unexpected edit {}", diff2[0]);
+        };
+        assert_eq!(node.signature(), ":Address");
+        assert_eq!(from_parent.children()[3].value(), Some("1"));
+        assert_eq!(to_parent.children()[2].value(), Some("2"));
+        assert_eq!(position, 2);
+    }
+
+    #[test]
+    fn test_diff_rename_detection() {
+        let text1 = r#"<Contact><Phone type="work"><Area>206</Area><Number>813-8698</Number></Phone></Contact>"#;
+        let text2 = r#"<Contact><Telephone type="work"><Area>206</Area><Number>813-8699</Number></Telephone></Contact>"#;
+        let tree1 = XTree::parse(text1).unwrap();
+        let tree2 = XTree::parse(text2).unwrap();
+
+        let diff1 = diff(&tree1, &tree2);
+        assert_eq!(diff1.len(), 2);
+        assert!(matches!(diff1[0], Edit::Delete(_)));
+        assert!(matches!(diff1[1], Edit::Insert { .. }));
+
+        let diff2 = diff_with_options(
+            &tree1,
+            &tree2,
+            DiffOptions::default().with_rename_detection(0.5),
+        );
+        assert_eq!(diff2.len(), 2);
+        assert!(matches!(
+            diff2[0],
+            Edit::Rename { old, new } if old.signature() == ":Phone" && new.signature() == ":Telephone"
+        ));
+        assert!(matches!(
+            diff2[1],
+            Edit::Update { old, new } if old.value() == Some("813-8698") && new.value() == Some("813-8699")
+        ));
+
+        // only 2 of 3 children are identical
+        let diff3 = diff_with_options(
+            &tree1,
+            &tree2,
+            DiffOptions::default().with_rename_detection(0.8),
+        );
+        assert_eq!(diff3.len(), 2);
+        assert!(matches!(diff3[0], Edit::Delete(_)));
+    }
+
+    #[test]
+    fn test_diff_replace_root() {
+        let text1 = r#"<Profile><Name>George</Name><Age>42</Age></Profile>"#;
+        let text2 = r#"<Person><Name>Fred</Name><Age>42</Age></Person>"#;
+        let tree1 = XTree::parse(text1).unwrap();
+        let tree2 = XTree::parse(text2).unwrap();
+
+        let diff1 = diff(&tree1, &tree2);
+        assert_eq!(diff1.len(), 1);
+        assert!(matches!(
+            diff1[0],
+            Edit::ReplaceRoot { old, new } if old == tree1.root() && new == tree2.root()
+        ));
+
+        let diff2 = diff_with_options(
+            &tree1,
+            &tree2,
+            DiffOptions::default().with_replaced_root_diff(true),
+        );
+        assert_eq!(diff2.len(), 2);
+        assert!(matches!(diff2[0], Edit::ReplaceRoot { .. }));
+        assert!(matches!(
+            diff2[1],
+            Edit::Update { old, new } if old.value() == Some("George") && new.value() == Some("Fred")
+        ));
+    }
+
+    #[test]
+    fn test_diff_ignore() {
+        let tree1 =
+            XTree::parse(r#"<a time="1"><id>7</id><b x="1" y="1"/><c><d>1</d></c><e>1</e></a>"#)
+                .unwrap();
+        let tree2 = XTree::parse(
+            r#"<a time="2"><id>8</id><b x="2" y="1"/><c><d>2</d><f/></c><e>2</e></a>"#,
+        )
+        .unwrap();
+        let options = DiffOptions::default()
+            .with_ignored(Selector::Attribute("time".to_string()))
+            .with_ignored(Selector::Name("id".to_string()))
+            .with_ignored(Selector::Glob("**/c".to_string()))
+            .with_ignored(Selector::XPath(XPath::parse("/a/b/@x").unwrap()));
+        let diff = diff_with_options(&tree1, &tree2, options.clone());
+        assert!(
+            matches!(diff.edits(), [Edit::Update { new, .. }] if new.parent().unwrap().signature() == ":e"),
+            "{:?}",
+            diff.edits()
+        );
+
+        // ignored nodes do not contribute to the hashes
+        let tree3 = XTree::parse(r#"<a><b y="1"/><e>2</e></a>"#).unwrap();
+        assert!(diff_with_options(&tree2, &tree3, options).is_empty());
+    }
+
+    #[test]
+    fn test_diff_key() {
+        let tree1 = XTree::parse(
+            r#"<list><Item id="1"><a>1</a><b>1</b></Item><Item id="2"><a>2</a><b>2</b></Item></list>"#,
+        )
+        .unwrap();
+        let tree2 = XTree::parse(
+            r#"<list><Item id="1"><a>2</a><b>2</b></Item><Item id="2"><a>1</a><b>1</b></Item></list>"#,
+        )
+        .unwrap();
+        // the cheapest edit script swaps the ids
+        let diff1 = diff(&tree1, &tree2);
+        assert!(
+            matches!(diff1.edits(), [Edit::Update { old, .. }, Edit::Update { .. }] if old.is_attribute())
+        );
+
+        let options = DiffOptions::default().with_key_for(
+            Selector::Name("Item".to_string()),
+            vec![KeyField::Attribute("id".to_string())],
+        );
+        let diff2 = diff_with_options(&tree1, &tree2, options);
+        assert_eq!(diff2.len(), 4);
+        for edit in diff2.iter() {
+            let Edit::Update { old, new } = edit else {
+                panic!("{edit}");
+            };
+            // same item, same field
+            assert!(old.is_text() && old.xpath() == new.xpath());
+        }
+
+        let tree1 = XTree::parse(
+            "<deps><dep><id>a</id><v>1</v></dep><dep><id>b</id><v>1</v></dep><dep><v>3</v></dep></deps>",
+        )
+        .unwrap();
+        let tree2 = XTree::parse(
+            "<deps><dep><id>c</id><v>1</v></dep><dep><id>b</id><v>2</v></dep><dep><v>4</v></dep></deps>",
+        )
+        .unwrap();
+        let options = DiffOptions::default().with_key_for(
+            Selector::Name("dep".to_string()),
+            vec![KeyField::Child("id".to_string())],
+        );
+        let edits: Vec<_> = diff_with_options(&tree1, &tree2, options)
+            .iter()
+            .map(|edit| format!("{edit:#}"))
+            .collect();
+        assert_eq!(
+            edits,
+            [
+                "delete node /deps/dep[1]",
+                r#"update node /deps/dep[2]/v/text(): "1" -> "2""#,
+                "insert node /deps/dep[1] to node /deps at position 0",
+                // without a key, matched as usual
+                r#"update node /deps/dep[3]/v/text(): "3" -> "4""#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_value_comparator() {
+        let tree1 = XTree::parse(
+            r#"<a><City>Seattle</City><Note>one  two</Note><p x="1.0" y="1.0"/><t>2024-05-01T12:00:00Z</t></a>"#,
+        )
+        .unwrap();
+        let tree2 = XTree::parse(
+            r#"<a><City>SEATTLE</City><Note>one two</Note><p x="1.00" y="1.02"/><t>2024-05-01T14:00+02:00</t></a>"#,
+        )
+        .unwrap();
+        assert_eq!(diff(&tree1, &tree2).len(), 5);
+
+        let options = DiffOptions::default()
+            .with_value_comparator(Selector::Name("City".to_string()), CaseInsensitive)
+            .with_value_comparator(Selector::Name("Note".to_string()), CollapseWhitespace)
+            .with_value_comparator(
+                Selector::Attribute("x".to_string()),
+                NumericTolerance::default(),
+            )
+            .with_value_comparator(
+                Selector::Attribute("y".to_string()),
+                NumericTolerance::default().with_absolute(0.05),
+            )
+            .with_value_comparator(Selector::Name("t".to_string()), DateTime);
+        let diff1 = diff_with_options(&tree1, &tree2, options.clone());
+        assert!(diff1.is_empty(), "{diff1:?}");
+
+        // values compared with a tolerance are still matched node by node
+        let tree3 = XTree::parse(r#"<a><p y="1.5"/><p y="3.0"/></a>"#).unwrap();
+        let tree4 = XTree::parse(r#"<a><p y="3.01"/><p y="1.6"/></a>"#).unwrap();
+        let edits: Vec<_> = diff_with_options(&tree3, &tree4, options.clone())
+            .iter()
+            .map(|edit| format!("{edit:#}"))
+            .collect();
+        assert_eq!(edits, [r#"update node /a/p[1]/@y: "1.5" -> "1.6""#]);
+    }
+
+    #[test]
+    fn test_diff_ordered_value_comparator() {
+        // the values compared with a tolerance share a hash, the comparator tells them apart
+        let options = DiffOptions::default()
+            .with_value_comparator(
+                Selector::Attribute("y".to_string()),
+                NumericTolerance::default().with_absolute(0.05),
+            )
+            .with_child_order(ChildOrder::Ordered);
+        let edits = |text1, text2| -> Vec<_> {
+            let tree1 = XTree::parse(text1).unwrap();
+            let tree2 = XTree::parse(text2).unwrap();
+            diff_with_options(&tree1, &tree2, options.clone())
+                .iter()
+                .map(|edit| format!("{edit:#}"))
+                .collect()
+        };
+        assert_eq!(
+            edits(
+                r#"<a><p y="1.0"/><p y="2.0"/></a>"#,
+                r#"<a><p y="2.01"/><p y="1.01"/></a>"#
+            ),
+            ["reorder node /a/p[1] from position 0 to position 1"]
+        );
+        assert_eq!(
+            edits(
+                r#"<a><p y="1.5"/><p y="3.0"/></a>"#,
+                r#"<a><p y="3.01"/><p y="1.6"/></a>"#
+            ),
+            [
+                "delete node /a/p[1]",
+                "insert node /a/p[2] to node /a at position 1",
+            ]
+        );
+        assert!(
+            edits(
+                r#"<a><p y="1.0"><q y="2"/></p></a>"#,
+                r#"<a><p y="1.01"><q y="2.02"/></p></a>"#
+            )
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_diff_numeric_tolerance() {
+        let tree1 = XTree::parse(
+            r#"<run><step t="0.1"><x>1.0000000001</x><x>-2.5e3</x></step><step t="0.2"><x>3</x><x>4</x></step><id>7</id></run>"#,
+        )
+        .unwrap();
+        let tree2 = XTree::parse(
+            r#"<run><step t="0.2"><x>3.0000000002</x><x>4</x></step><step t="0.10000000000000001"><x>1</x><x>-2500</x></step><id>8</id></run>"#,
+        )
+        .unwrap();
+        let tolerance = NumericTolerance::default().with_relative(1e-9);
+        let options = DiffOptions::default()
+            .with_numeric_tolerance(tolerance.clone())
+            .with_child_order_for(Selector::Name("run".to_string()), ChildOrder::Ordered);
+        let edits: Vec<_> = diff_with_options(&tree1, &tree2, options)
+            .iter()
+            .map(|edit| format!("{edit:#}"))
+            .collect();
+        assert_eq!(
+            edits,
+            [
+                r#"update node /run/id/text(): "7" -> "8""#,
+                "reorder node /run/step[1] from position 0 to position 1",
+            ]
+        );
+
+        // a comparator rule takes precedence
+        let options = DiffOptions::default()
+            .with_numeric_tolerance(tolerance)
+            .with_value_comparator(Selector::Name("x".to_string()), NumericTolerance::default());
+        let edits: Vec<_> = diff_with_options(&tree1, &tree2, options)
+            .iter()
+            .map(|edit| format!("{edit:#}"))
+            .collect();
+        assert_eq!(
+            edits,
+            [
+                r#"update node /run/step[1]/x[1]/text(): "1.0000000001" -> "1""#,
+                r#"update node /run/step[2]/x[1]/text(): "3" -> "3.0000000002""#,
+                r#"update node /run/id/text(): "7" -> "8""#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_insert_position() {
+        let text1 = r#"<List><Item>a</Item><Item>c</Item></List>"#;
+        let text2 = r#"<List><Item>a</Item><Item>b</Item><Item>c</Item><Item>d</Item></List>"#;
+        let tree1 = XTree::parse(text1).unwrap();
+        let tree2 = XTree::parse(text2).unwrap();
+        for options in [
+            DiffOptions::default(),
+            DiffOptions::default().with_child_order(ChildOrder::Ordered),
+        ] {
+            let inserted: Vec<_> = diff_with_options(&tree1, &tree2, options)
+                .into_iter()
+                .map(|e| match e {
+                    Edit::Insert {
+                        child_node,
+                        position,
+                        ..
+                    } => (
+                        child_node.children()[0].value().unwrap().to_string(),
+                        position,
+                    ),
+                    _ => panic!("unexpected edit {e}"),
+                })
+                .collect();
+            assert_eq!(inserted, vec![("b".to_string(), 1), ("d".to_string(), 3)]);
+        }
+    }
+
+    #[test]
+    fn test_diff_invert() {
+        let text1 = fs::read_to_string("test/file1.xml").unwrap();
+        let text2 = fs::read_to_string("test/file2.xml").unwrap();
+        let moved1 =
+            r#"<r><a k="1"><b/><c>x</c></a><d>1</d><e/><Phone><n>1</n><m>2</m></Phone></r>"#;
+        let moved2 = r#"<r><d>2</d><a k="2"><c>y</c><b>new</b></a><e><c>x</c></e><Telephone><n>1</n><m>2</m></Telephone></r>"#;
+        let cases = [
+            (text1.as_str(), text2.as_str(), DiffOptions::default()),
+            (
+                moved1,
+                moved2,
+                DiffOptions::default()
+                    .with_move_detection(true)
+                    .with_rename_detection(0.5),
+            ),
+            (
+                moved1,
+                moved2,
+                DiffOptions::default().with_child_order(ChildOrder::Ordered),
+            ),
+            (
+                "<a><b>1</b></a>",
+                "<c><b>2</b><d/></c>",
+                DiffOptions::default().with_replaced_root_diff(true),
+            ),
+        ];
+        for (text1, text2, options) in cases {
+            let tree1 = XTree::parse(text1).unwrap();
+            let tree2 = XTree::parse(text2).unwrap();
+            let diff = diff_with_options(&tree1, &tree2, options.clone());
+            let inverted = diff.invert();
+            assert_eq!(inverted.len(), diff.len());
+            for (edit, inverted_edit) in diff.iter().zip(inverted.iter()) {
+                match (edit, inverted_edit) {
+                    (Edit::Insert { child_node, .. }, Edit::Delete(node)) => {
+                        assert_eq!(child_node, node)
+                    }
+                    (Edit::Delete(node), Edit::Insert { child_node, .. }) => {
+                        assert_eq!(child_node, node)
+                    }
+                    (
+                        Edit::Update { old, new },
+                        Edit::Update {
+                            old: new2,
+                            new: old2,
+                        },
+                    )
+                    | (
+                        Edit::Rename { old, new },
+                        Edit::Rename {
+                            old: new2,
+                            new: old2,
+                        },
+                    ) => {
+                        assert_eq!((old, new), (old2, new2))
+                    }
+                    (Edit::Move { .. }, Edit::Move { .. })
+                    | (Edit::Reorder { .. }, Edit::Reorder { .. })
+                    | (Edit::ReplaceRoot { .. }, Edit::ReplaceRoot { .. }) => {}
+                    _ => panic!("{edit} inverted to {inverted_edit}"),
+                }
+            }
+
+            // applying the inverted edit script to tree2 gives back tree1
+            let patched = crate::patch::patch(&tree2, &inverted);
+            let patched_tree = XTree::parse(&patched).unwrap();
+            assert!(
+                diff_with_options(&patched_tree, &tree1, options).is_empty(),
+                "{patched}"
+            );
+        }
+    }
+
     #[test]
     fn test_diff() {
         let text1 = fs::read_to_string("test/file1.xml").unwrap();
@@ -231,4 +2005,22 @@ mod test {
             println!("{}", e);
         }
     }
+
+    #[test]
+    fn test_edit_display_xpath() {
+        let tree1 = XTree::parse(r#"<a><b x="1"/><b/></a>"#).unwrap();
+        let tree2 = XTree::parse(r#"<a><b x="2"/><b><c/></b></a>"#).unwrap();
+        let mut lines: Vec<_> = diff(&tree1, &tree2)
+            .iter()
+            .map(|edit| format!("{edit:#}"))
+            .collect();
+        lines.sort();
+        assert_eq!(
+            lines,
+            [
+                "insert node /a/b[2]/c to node /a/b[2] at position 0",
+                r#"update node /a/b[1]/@x: "1" -> "2""#,
+            ]
+        );
+    }
 }
@@ -0,0 +1,330 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    diff::{DiffOptions, diff_with_options},
+    tree::XTree,
+};
+
+/// Options to compare two directories.
+#[derive(Debug, Clone)]
+pub struct DirDiffOptions {
+    diff_options: DiffOptions,
+    extensions: Vec<String>,
+}
+
+impl Default for DirDiffOptions {
+    fn default() -> Self {
+        Self {
+            diff_options: DiffOptions::default(),
+            extensions: vec!["xml".to_string()],
+        }
+    }
+}
+
+impl DirDiffOptions {
+    /// Set the options used to compare each pair of files.
+    pub fn with_diff_options(mut self, diff_options: DiffOptions) -> Self {
+        self.diff_options = diff_options;
+        self
+    }
+
+    /// Set the extensions of the files to compare, `xml` by default. Other files are ignored.
+    pub fn with_extensions(mut self, extensions: &[&str]) -> Self {
+        self.extensions = extensions.iter().map(|e| e.to_string()).collect();
+        self
+    }
+}
+
+/// How a file differs between both directories.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileStatus {
+    /// The file is only in the second directory.
+    Added,
+    /// The file is only in the first directory.
+    Removed,
+    /// The file is in both directories, and has no differences.
+    Same,
+    /// The file is in both directories, and its edit script has this many edits.
+    Changed(usize),
+    /// One of the files cannot be read or parsed.
+    Error(String),
+}
+
+/// A file of the compared directories.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileDiff {
+    /// Path relative to the compared directories.
+    pub path: PathBuf,
+    pub status: FileStatus,
+}
+
+/// Result of the comparison of two directories: the files of both directories, sorted by path.
+#[derive(Debug, Clone, Default)]
+pub struct DirDiff {
+    /// The files of either directory, with how they differ, sorted by path.
+    pub files: Vec<FileDiff>,
+}
+
+impl DirDiff {
+    /// Check whether all the files are the same in both directories.
+    pub fn is_same(&self) -> bool {
+        self.files.iter().all(|f| f.status == FileStatus::Same)
+    }
+
+    /// Total number of edits of the changed files.
+    pub fn edit_count(&self) -> usize {
+        self.files
+            .iter()
+            .map(|f| match f.status {
+                FileStatus::Changed(n) => n,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    fn count(&self, selected: impl Fn(&FileStatus) -> bool) -> usize {
+        self.files.iter().filter(|f| selected(&f.status)).count()
+    }
+}
+
+/// One line per file which is not the same, then a summary line.
+impl Display for DirDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for file in &self.files {
+            let path = file.path.display();
+            match &file.status {
+                FileStatus::Added => writeln!(f, "added: {path}")?,
+                FileStatus::Removed => writeln!(f, "removed: {path}")?,
+                FileStatus::Same => {}
+                FileStatus::Changed(n) => writeln!(f, "changed: {path} ({n} edits)")?,
+                FileStatus::Error(e) => writeln!(f, "error: {path}: {e}")?,
+            }
+        }
+        write!(
+            f,
+            "{} files compared, {} changed ({} edits), {} added, {} removed, {} errors",
+            self.files.len(),
+            self.count(|s| matches!(s, FileStatus::Changed(_))),
+            self.edit_count(),
+            self.count(|s| *s == FileStatus::Added),
+            self.count(|s| *s == FileStatus::Removed),
+            self.count(|s| matches!(s, FileStatus::Error(_))),
+        )
+    }
+}
+
+/// Compare the XML files of two directories, paired by their path relative to the directories.
+/// Symbolic links are followed, except those leading back to a directory being listed. A file or
+/// subdirectory which cannot be listed, such as a dangling symbolic link, is reported with
+/// [FileStatus::Error].
+pub fn diff_dirs(dir1: &Path, dir2: &Path) -> io::Result<DirDiff> {
+    diff_dirs_with_options(dir1, dir2, &DirDiffOptions::default())
+}
+
+/// Same as [diff_dirs], but with options to control the comparison.
+pub fn diff_dirs_with_options(
+    dir1: &Path,
+    dir2: &Path,
+    options: &DirDiffOptions,
+) -> io::Result<DirDiff> {
+    let mut files1 = Listing::new();
+    list_files(dir1, Path::new(""), options, &mut files1, &mut Vec::new())?;
+    let mut files2 = Listing::new();
+    list_files(dir2, Path::new(""), options, &mut files2, &mut Vec::new())?;
+
+    let paths: BTreeSet<_> = files1.keys().chain(files2.keys()).collect();
+    let files = paths
+        .into_iter()
+        .map(|path| {
+            let status = match (files1.get(path), files2.get(path)) {
+                (Some(Some(error)), _) | (_, Some(Some(error))) => FileStatus::Error(error.clone()),
+                (Some(None), None) => FileStatus::Removed,
+                (None, Some(None)) => FileStatus::Added,
+                _ => diff_files(&dir1.join(path), &dir2.join(path), options)
+                    .unwrap_or_else(FileStatus::Error),
+            };
+            FileDiff {
+                path: path.clone(),
+                status,
+            }
+        })
+        .collect();
+    Ok(DirDiff { files })
+}
+
+/// Paths of the files found under a directory, relative to it, with the error met to list them.
+type Listing = BTreeMap<PathBuf, Option<String>>;
+
+/// Collect the paths, relative to `root`, of the selected files under `root/relative`. The
+/// subdirectories and files which cannot be listed are collected with their error. `ancestors`
+/// holds the canonical paths of the directories being listed, down to `root/relative`.
+fn list_files(
+    root: &Path,
+    relative: &Path,
+    options: &DirDiffOptions,
+    files: &mut Listing,
+    ancestors: &mut Vec<PathBuf>,
+) -> io::Result<()> {
+    let dir = root.join(relative);
+    // a cycle of symbolic links leads back to a directory being listed, while a directory reached
+    // through several paths is listed under each of them
+    let canonical = fs::canonicalize(&dir)?;
+    if ancestors.contains(&canonical) {
+        return Ok(());
+    }
+    ancestors.push(canonical);
+    let listed = list_entries(root, relative, options, files, ancestors);
+    ancestors.pop();
+    listed
+}
+
+fn list_entries(
+    root: &Path,
+    relative: &Path,
+    options: &DirDiffOptions,
+    files: &mut Listing,
+    ancestors: &mut Vec<PathBuf>,
+) -> io::Result<()> {
+    for entry in fs::read_dir(root.join(relative))? {
+        let entry = entry?;
+        let path = relative.join(entry.file_name());
+        let selected = path
+            .extension()
+            .is_some_and(|e| options.extensions.iter().any(|x| e == x.as_str()));
+        // follow symbolic links
+        match fs::metadata(entry.path()) {
+            Ok(metadata) if metadata.is_dir() => {
+                if let Err(error) = list_files(root, &path, options, files, ancestors) {
+                    files.insert(path, Some(error.to_string()));
+                }
+            }
+            Ok(_) if selected => {
+                files.insert(path, None);
+            }
+            // e.g. a dangling symbolic link
+            Err(error) if selected => {
+                files.insert(path, Some(error.to_string()));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn diff_files(path1: &Path, path2: &Path, options: &DirDiffOptions) -> Result<FileStatus, String> {
+    let read = |path: &Path| fs::read_to_string(path).map_err(|e| e.to_string());
+    let (text1, text2) = (read(path1)?, read(path2)?);
+    let tree1 = XTree::parse(&text1).map_err(|e| e.to_string())?;
+    let tree2 = XTree::parse(&text2).map_err(|e| e.to_string())?;
+    let diff = diff_with_options(&tree1, &tree2, options.diff_options.clone());
+    Ok(if diff.is_empty() {
+        FileStatus::Same
+    } else {
+        FileStatus::Changed(diff.len())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn write_files(dir: &Path, files: &[(&str, &str)]) {
+        for (path, content) in files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_diff_dirs() {
+        let root = std::env::temp_dir().join(format!("x-diff-rs-test-dirs-{}", std::process::id()));
+        let (dir1, dir2) = (root.join("1"), root.join("2"));
+        write_files(
+            &dir1,
+            &[
+                ("same.xml", "<a><b/><c/></a>"),
+                ("sub/changed.xml", "<a x='1'><b/></a>"),
+                ("removed.xml", "<a/>"),
+                ("broken.xml", "<a>"),
+                ("ignored.txt", "1"),
+            ],
+        );
+        write_files(
+            &dir2,
+            &[
+                ("same.xml", "<a><c/><b/></a>"),
+                ("sub/changed.xml", "<a x='2'><c/></a>"),
+                ("sub/added.xml", "<a/>"),
+                ("broken.xml", "<a/>"),
+                ("ignored.txt", "2"),
+            ],
+        );
+
+        let diff = diff_dirs(&dir1, &dir2).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+        let files: Vec<_> = diff
+            .files
+            .iter()
+            .map(|f| {
+                (
+                    f.path.to_str().unwrap().replace('\\', "/"),
+                    f.status.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(files.len(), 5);
+        assert!(matches!(files[0], (ref p, FileStatus::Error(_)) if p == "broken.xml"));
+        assert_eq!(
+            files[1..],
+            [
+                ("removed.xml".to_string(), FileStatus::Removed),
+                ("same.xml".to_string(), FileStatus::Same),
+                ("sub/added.xml".to_string(), FileStatus::Added),
+                ("sub/changed.xml".to_string(), FileStatus::Changed(3)),
+            ]
+        );
+        assert!(!diff.is_same());
+        assert!(
+            diff.to_string()
+                .ends_with("5 files compared, 1 changed (3 edits), 1 added, 1 removed, 1 errors")
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_diff_dirs_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let root =
+            std::env::temp_dir().join(format!("x-diff-rs-test-links-{}", std::process::id()));
+        let (dir1, dir2) = (root.join("1"), root.join("2"));
+        write_files(&dir1, &[("sub/a.xml", "<a/>")]);
+        write_files(&dir2, &[("sub/a.xml", "<a/>")]);
+        for dir in [&dir1, &dir2] {
+            // a cycle
+            symlink(dir, dir.join("sub/loop")).unwrap();
+        }
+        symlink(dir1.join("missing.xml"), dir1.join("dangling.xml")).unwrap();
+        // a directory reached through two paths on one side only
+        symlink(dir1.join("sub"), dir1.join("alias")).unwrap();
+        write_files(&dir2, &[("alias/a.xml", "<a/>")]);
+
+        let diff = diff_dirs(&dir1, &dir2).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+        let files: Vec<_> = diff
+            .files
+            .iter()
+            .map(|f| (f.path.to_str().unwrap(), &f.status))
+            .collect();
+        assert_eq!(files.len(), 3);
+        assert_eq!(files[0], ("alias/a.xml", &FileStatus::Same));
+        assert!(matches!(files[1], ("dangling.xml", FileStatus::Error(_))));
+        assert_eq!(files[2], ("sub/a.xml", &FileStatus::Same));
+    }
+}
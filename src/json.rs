@@ -0,0 +1,101 @@
+use crate::{diff::Diff, record::EditRecord, tree::XTree};
+
+/// Write the edits of a diff as a JSON array of their [EditRecord]s, one edit per line.
+///
+/// The records are serialized with their `serde` implementation: each edit is an object with the
+/// fields `kind`, `old`, `new`, `parent`, `from_parent` and `position`, where a node is an object
+/// with the fields `path`, `xpath`, `kind`, `name`, `namespace`, `value` and `range`, a range being
+/// an object with the fields `start` and `end`. Kinds are in snake case, such as `"replace_root"`,
+/// and missing values are `null`.
+pub fn to_json(diff: &Diff) -> String {
+    records_to_json(&diff.to_records())
+}
+
+/// Write the insertion of a whole document as a JSON array, as [to_json] does for a diff, see
+/// [EditRecord::insert_document].
+pub fn insert_document_to_json(tree: &XTree) -> String {
+    records_to_json(&[EditRecord::insert_document(tree)])
+}
+
+/// Write the deletion of a whole document as a JSON array, as [to_json] does for a diff, see
+/// [EditRecord::delete_document].
+pub fn delete_document_to_json(tree: &XTree) -> String {
+    records_to_json(&[EditRecord::delete_document(tree)])
+}
+
+/// Write edit records as a JSON array, one edit per line, see [to_json].
+pub fn records_to_json(records: &[EditRecord]) -> String {
+    if records.is_empty() {
+        return "[]".to_string();
+    }
+    let lines: Vec<_> = records
+        .iter()
+        .map(|record| serde_json::to_string(record).unwrap())
+        .collect();
+    format!("[\n{}\n]", lines.join(",\n"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::diff::{DiffOptions, diff, diff_with_options};
+
+    #[test]
+    fn test_to_json() {
+        let tree1 = XTree::parse(r#"<a><b x="1"/><b>"text"</b></a>"#).unwrap();
+        let tree2 = XTree::parse(r#"<a><b x="2"/><b>"text"</b><c/></a>"#).unwrap();
+        let lines: Vec<_> = to_json(&diff(&tree1, &tree2))
+            .lines()
+            .map(str::to_string)
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "[",
+                r#"{"kind":"update","old":{"path":[0,0],"xpath":"/a/b[1]/@x","kind":"attribute","name":"x","namespace":null,"value":"1","range":{"start":6,"end":11}},"new":{"path":[0,0],"xpath":"/a/b[1]/@x","kind":"attribute","name":"x","namespace":null,"value":"2","range":{"start":6,"end":11}},"parent":null,"from_parent":null,"position":null},"#,
+                r#"{"kind":"insert","old":null,"new":{"path":[2],"xpath":"/a/c","kind":"element","name":"c","namespace":null,"value":null,"range":{"start":26,"end":30}},"parent":{"path":[],"xpath":"/a","kind":"element","name":"a","namespace":null,"value":null,"range":{"start":0,"end":30}},"from_parent":null,"position":2}"#,
+                "]",
+            ]
+        );
+        assert_eq!(to_json(&diff(&tree1, &tree1)), "[]");
+    }
+
+    #[test]
+    fn test_to_json_trimmed() {
+        let tree1 = XTree::parse("<a><b>\n\t\"Fred\"\n   </b></a>").unwrap();
+        let tree2 = XTree::parse("<a><b>George</b></a>").unwrap();
+        let json = to_json(&diff(&tree1, &tree2));
+        assert!(
+            json.contains(r#""xpath":"/a/b/text()","kind":"text","name":null,"namespace":null,"value":"\"Fred\"""#),
+            "{json}"
+        );
+        assert!(json.contains(r#""value":"George""#), "{json}");
+    }
+
+    #[test]
+    fn test_document_to_json() {
+        let tree = XTree::parse("<a><b/></a>").unwrap();
+        assert_eq!(
+            insert_document_to_json(&tree),
+            "[\n".to_string()
+                + r#"{"kind":"insert","old":null,"new":{"path":[],"xpath":"/a","kind":"element","name":"a","namespace":null,"value":null,"range":{"start":0,"end":11}},"parent":null,"from_parent":null,"position":0}"#
+                + "\n]"
+        );
+        assert!(delete_document_to_json(&tree).starts_with(
+            r#"[
+{"kind":"delete","old":{"path":[],"xpath":"/a""#
+        ));
+    }
+
+    #[test]
+    fn test_to_json_records() {
+        // the JSON reads back as the records
+        let tree1 = XTree::parse(r#"<a xmlns:n="urn:n"><b n:x="1"><c/></b><d/></a>"#).unwrap();
+        let tree2 =
+            XTree::parse(r#"<a xmlns:n="urn:n"><b n:x="2"/><d><c/></d><e>t</e></a>"#).unwrap();
+        let options = DiffOptions::default().with_move_detection(true);
+        let diff = diff_with_options(&tree1, &tree2, options);
+        let parsed: Vec<EditRecord> = serde_json::from_str(&to_json(&diff)).unwrap();
+        assert_eq!(parsed, diff.to_records());
+    }
+}
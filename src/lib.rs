@@ -92,3 +92,36 @@ pub mod tree;
 
 /// Diff calculation
 pub mod diff;
+
+/// Cost models for the edit operations.
+pub mod cost;
+
+/// Comparison of attribute and text values.
+pub mod comparator;
+
+/// Application of edit scripts to XML documents.
+pub mod patch;
+
+/// Three-way merge of XML documents.
+pub mod merge;
+
+/// Comparison of directories of XML files.
+pub mod dir;
+
+/// Owned representation of edit scripts, serializable with the `serde` feature.
+pub mod record;
+
+/// JSON output of edit scripts, with the `serde` feature.
+#[cfg(feature = "serde")]
+pub mod json;
+
+/// XML Patch (RFC 5261) output of edit scripts.
+pub mod xml_patch;
+
+/// Selection of nodes to apply per-element options.
+pub mod selector;
+
+/// Selection of nodes with a subset of XPath.
+pub mod xpath;
+
+mod matching;
@@ -0,0 +1,143 @@
+/// Solve the minimum-cost bipartite matching between `rows` and `columns` where any row or column
+/// may also be left unmatched.
+///
+/// `pair_costs[i][j]` is the cost of matching row `i` with column `j`, or `None` if they cannot be
+/// matched. `row_costs[i]` and `column_costs[j]` are the costs of leaving row `i` and column `j`
+/// unmatched. Returns, for each row, the index of the column it is matched with.
+pub(crate) fn min_cost_matching(
+    pair_costs: &[Vec<Option<u64>>],
+    row_costs: &[u64],
+    column_costs: &[u64],
+) -> Vec<Option<usize>> {
+    let rows = row_costs.len();
+    let columns = column_costs.len();
+    if rows == 0 || columns == 0 {
+        return vec![None; rows];
+    }
+
+    // Square matrix of size rows + columns:
+    // - top-left: row i matched with column j
+    // - top-right: row i left unmatched (only on the diagonal)
+    // - bottom-left: column j left unmatched (only on the diagonal)
+    // - bottom-right: dummy pairs, free
+    // `None` is a forbidden pair. The diagonals always leave a perfect matching of allowed pairs.
+    // Costs are widened to i128, so that neither they nor the sums of potentials can overflow.
+    let n = rows + columns;
+    let cost = |i: usize, j: usize| -> Option<i128> {
+        match (i < rows, j < columns) {
+            (true, true) => pair_costs[i][j].map(i128::from),
+            (true, false) if j - columns == i => Some(i128::from(row_costs[i])),
+            (false, true) if i - rows == j => Some(i128::from(column_costs[j])),
+            (false, false) => Some(0),
+            _ => None,
+        }
+    };
+
+    // Hungarian algorithm with potentials, 1-based indices, O(n^3).
+    let mut u = vec![0i128; n + 1];
+    let mut v = vec![0i128; n + 1];
+    let mut p = vec![0usize; n + 1];
+    let mut way = vec![0usize; n + 1];
+    for i in 1..=n {
+        p[0] = i;
+        let mut j0 = 0;
+        // minimum reduced cost to reach each column, `None` while unreachable
+        let mut minv: Vec<Option<i128>> = vec![None; n + 1];
+        let mut used = vec![false; n + 1];
+        loop {
+            used[j0] = true;
+            let i0 = p[j0];
+            let mut delta = None;
+            let mut j1 = 0;
+            for j in 1..=n {
+                if !used[j] {
+                    if let Some(c) = cost(i0 - 1, j - 1) {
+                        let cur = c - u[i0] - v[j];
+                        if minv[j].is_none_or(|m| cur < m) {
+                            minv[j] = Some(cur);
+                            way[j] = j0;
+                        }
+                    }
+                    if let Some(m) = minv[j]
+                        && delta.is_none_or(|d| m < d)
+                    {
+                        delta = Some(m);
+                        j1 = j;
+                    }
+                }
+            }
+            let delta = delta.expect("the diagonals keep a column reachable");
+            for j in 0..=n {
+                if used[j] {
+                    u[p[j]] += delta;
+                    v[j] -= delta;
+                } else if let Some(m) = &mut minv[j] {
+                    *m -= delta;
+                }
+            }
+            j0 = j1;
+            if p[j0] == 0 {
+                break;
+            }
+        }
+        loop {
+            let j1 = way[j0];
+            p[j0] = p[j1];
+            j0 = j1;
+            if j0 == 0 {
+                break;
+            }
+        }
+    }
+
+    let mut result = vec![None; rows];
+    for j in 1..=n {
+        let i = p[j];
+        if i >= 1 && i <= rows && j <= columns && pair_costs[i - 1][j - 1].is_some() {
+            result[i - 1] = Some(j - 1);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_min_cost_matching_prefers_cheaper_total() {
+        // A greedy choice of the cheapest pair (0, 0) forces the expensive pair (1, 1).
+        let pair_costs = vec![vec![Some(1), Some(2)], vec![Some(2), Some(10)]];
+        let matching = min_cost_matching(&pair_costs, &[20, 20], &[20, 20]);
+        assert_eq!(matching, vec![Some(1), Some(0)]);
+    }
+
+    #[test]
+    fn test_min_cost_matching_leaves_expensive_pairs_unmatched() {
+        let pair_costs = vec![vec![Some(10)], vec![None]];
+        let matching = min_cost_matching(&pair_costs, &[1, 1], &[1]);
+        assert_eq!(matching, vec![None, None]);
+    }
+
+    #[test]
+    fn test_min_cost_matching_forbidden_pairs() {
+        let pair_costs = vec![vec![None, Some(3)], vec![None, None]];
+        let matching = min_cost_matching(&pair_costs, &[5, 5], &[5, 5]);
+        assert_eq!(matching, vec![Some(1), None]);
+    }
+
+    #[test]
+    fn test_min_cost_matching_large_costs() {
+        // costs beyond i64 and close to u64::MAX must neither wrap nor look forbidden
+        let big = u64::MAX - 1;
+        let pair_costs = vec![vec![Some(big), Some(1)], vec![Some(1), None]];
+        let matching = min_cost_matching(&pair_costs, &[u64::MAX, u64::MAX], &[u64::MAX, u64::MAX]);
+        assert_eq!(matching, vec![Some(1), Some(0)]);
+        let pair_costs = vec![vec![Some(big)]];
+        assert_eq!(min_cost_matching(&pair_costs, &[1], &[1]), vec![None]);
+        assert_eq!(
+            min_cost_matching(&pair_costs, &[u64::MAX], &[u64::MAX]),
+            vec![Some(0)]
+        );
+    }
+}
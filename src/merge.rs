@@ -0,0 +1,532 @@
+use std::{collections::HashSet, fmt::Display};
+
+use crate::{
+    diff::{Diff, DiffOptions, Edit, diff_with_options},
+    patch::Patcher,
+    tree::{XNode, XTree},
+};
+
+/// How both sides changed the same node of the base document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictKind {
+    /// Both sides updated the value of the node differently.
+    Update,
+    /// Both sides renamed the element differently.
+    Rename,
+    /// Both sides moved the subtree to different places.
+    Move,
+    /// Both sides moved the subtree to different positions among its siblings.
+    Reorder,
+    /// Both sides replaced the root element differently.
+    ReplaceRoot,
+    /// Our side deleted the subtree, their side changed it.
+    DeletedByOurs,
+    /// Their side deleted the subtree, our side changed it.
+    DeletedByTheirs,
+}
+
+/// A node of the base document changed by both sides in incompatible ways.
+#[derive(Debug, Clone)]
+pub struct Conflict<'a, 'base> {
+    /// The node of the base document.
+    pub node: XNode<'a, 'base>,
+    pub kind: ConflictKind,
+    /// Our conflicting edit, as displayed by [Edit].
+    pub ours: String,
+    /// Their conflicting edit, as displayed by [Edit].
+    pub theirs: String,
+}
+
+impl Display for Conflict<'_, '_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.kind {
+            ConflictKind::Update => "updated by both sides",
+            ConflictKind::Rename => "renamed by both sides",
+            ConflictKind::Move => "moved by both sides",
+            ConflictKind::Reorder => "reordered by both sides",
+            ConflictKind::ReplaceRoot => "replaced by both sides",
+            ConflictKind::DeletedByOurs => "deleted by ours, changed by theirs",
+            ConflictKind::DeletedByTheirs => "changed by ours, deleted by theirs",
+        };
+        write!(
+            f,
+            "conflict on node {}, {}: ours: {}, theirs: {}",
+            self.node.id(),
+            kind,
+            self.ours,
+            self.theirs
+        )
+    }
+}
+
+/// Result of a three-way merge.
+#[derive(Debug, Clone)]
+pub struct Merge<'a, 'base> {
+    /// The merged document. The elements with conflicts are replaced by both of their versions
+    /// between conflict markers, as `git merge` does, so the document is not well-formed then.
+    pub text: String,
+    pub conflicts: Vec<Conflict<'a, 'base>>,
+}
+
+impl Merge<'_, '_> {
+    /// Check whether all changes were merged without conflicts.
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+/// Merge the changes made to `base` by `ours` and by `theirs`.
+///
+/// Both edit scripts from `base` are applied to the text of `base`, see [crate::patch::patch]. The
+/// changes made by both sides are applied once.
+pub fn merge<'a, 'base>(
+    base: &'base XTree<'base>,
+    ours: &'a XTree,
+    theirs: &'a XTree,
+) -> Merge<'a, 'base> {
+    merge_with_options(base, ours, theirs, DiffOptions::default())
+}
+
+/// Same as [merge], but with options to control the calculation of both edit scripts.
+pub fn merge_with_options<'a, 'base>(
+    base: &'base XTree<'base>,
+    ours: &'a XTree,
+    theirs: &'a XTree,
+    options: DiffOptions,
+) -> Merge<'a, 'base> {
+    let ours = diff_with_options(base, ours, options.clone());
+    let theirs = diff_with_options(base, theirs, options);
+
+    let mut skipped_ours = vec![false; ours.len()];
+    let mut skipped_theirs = vec![false; theirs.len()];
+    let mut conflicts: Vec<Conflict> = Vec::new();
+    for (i, e1) in ours.iter().enumerate() {
+        for (j, e2) in theirs.iter().enumerate() {
+            if same_change(e1, e2) {
+                skipped_theirs[j] = true;
+            } else if let Some((node, kind)) = conflict(e1, e2) {
+                skipped_ours[i] = true;
+                skipped_theirs[j] = true;
+                if !conflicts.iter().any(|c| c.node == node) {
+                    conflicts.push(Conflict {
+                        node,
+                        kind,
+                        ours: e1.to_string(),
+                        theirs: e2.to_string(),
+                    });
+                }
+            }
+        }
+    }
+
+    // conflict markers cannot go inside a tag, and replace the outermost elements with conflicts
+    let elements: Vec<_> = conflicts
+        .iter()
+        .map(|conflict| {
+            if conflict.node.is_element() {
+                conflict.node
+            } else {
+                conflict.node.parent().unwrap()
+            }
+        })
+        .collect();
+    let mut marked: Vec<XNode> = Vec::new();
+    for element in &elements {
+        if !marked.contains(element)
+            && !elements
+                .iter()
+                .any(|e| e != element && element.is_within(*e))
+        {
+            marked.push(*element);
+        }
+    }
+    let in_conflict = |node: XNode| marked.iter().any(|m| node.is_within(*m));
+
+    let mut patcher = Patcher::new(base.text());
+    for (diff, skipped) in [(&ours, &skipped_ours), (&theirs, &skipped_theirs)] {
+        let mut edits: Vec<_> = diff
+            .iter()
+            .zip(skipped)
+            .filter(|(_, s)| !**s)
+            .map(|(e, _)| e)
+            // moving a node out of a conflict is part of the conflict, both versions of the node
+            // are between the markers
+            .filter(|e| match e {
+                Edit::Move { node, .. } | Edit::Reorder { old: node, .. } => !in_conflict(*node),
+                _ => true,
+            })
+            .collect();
+        // the new children of a parent keep their order
+        edits.sort_by_key(|e| new_place(e).map(|(_, position)| position));
+        for e in edits {
+            // positions are in the version of each side, both sides are patched on the base
+            match new_place(e) {
+                Some((parent, position)) => {
+                    patcher.add_after(e, base_position(diff, parent, position))
+                }
+                None => patcher.add(e),
+            }
+        }
+    }
+    for element in marked {
+        let (range, text) = conflict_markers(
+            base.text(),
+            element,
+            &version(&ours, element),
+            &version(&theirs, element),
+        );
+        patcher.replace_covering(range, text);
+    }
+
+    Merge {
+        text: patcher.finish(),
+        conflicts,
+    }
+}
+
+/// Get the nodes of the base document changed by an edit.
+fn changed_nodes<'a, 'base>(edit: &Edit<'a, 'base, '_>) -> Vec<XNode<'a, 'base>> {
+    match *edit {
+        Edit::Insert { to_node, .. } => vec![to_node],
+        Edit::Delete(node) => vec![node],
+        Edit::Update { old, .. }
+        | Edit::ReplaceRoot { old, .. }
+        | Edit::Reorder { old, .. }
+        | Edit::Rename { old, .. } => vec![old],
+        Edit::Move {
+            node, to_parent, ..
+        } => vec![node, to_parent],
+    }
+}
+
+/// Get the new parent of the element or text inserted, reordered or moved by an edit, and its
+/// position among the children of the new parent in the other tree.
+fn new_place<'a, 'base>(edit: &Edit<'a, 'base, '_>) -> Option<(XNode<'a, 'base>, usize)> {
+    match *edit {
+        Edit::Insert {
+            child_node,
+            to_node,
+            position,
+        } if !child_node.is_attribute() => Some((to_node, position)),
+        Edit::Reorder { old, to, .. } => Some((old.parent().unwrap(), to)),
+        Edit::Move {
+            to_parent,
+            position,
+            ..
+        } => Some((to_parent, position)),
+        _ => None,
+    }
+}
+
+/// Convert the position of a new child of `parent` in the version of one side into the number of
+/// children of `parent` in the base document that it follows, not counting attributes: the new
+/// child follows the same children kept in place by that side.
+fn base_position(diff: &Diff, parent: XNode, position: usize) -> usize {
+    let mut new_positions = HashSet::new();
+    let mut displaced = HashSet::new();
+    for edit in diff {
+        if let Some((new_parent, position)) = new_place(edit)
+            && new_parent == parent
+        {
+            new_positions.insert(position);
+        }
+        if let Edit::Delete(node) | Edit::Reorder { old: node, .. } | Edit::Move { node, .. } =
+            *edit
+        {
+            displaced.insert(node.id().to_string());
+        }
+    }
+    let kept_before = (0..position).filter(|p| !new_positions.contains(p)).count();
+    parent
+        .children()
+        .into_iter()
+        .filter(|child| !child.is_attribute())
+        .enumerate()
+        .filter(|(_, child)| !displaced.contains(&child.id().to_string()))
+        .map(|(i, _)| i + 1)
+        .take(kept_before)
+        .last()
+        .unwrap_or(0)
+}
+
+fn same_change(e1: &Edit, e2: &Edit) -> bool {
+    match (e1, e2) {
+        (
+            Edit::Insert {
+                child_node: c1,
+                to_node: t1,
+                ..
+            },
+            Edit::Insert {
+                child_node: c2,
+                to_node: t2,
+                ..
+            },
+        ) => t1 == t2 && c1.source() == c2.source(),
+        (Edit::Delete(n1), Edit::Delete(n2)) => n1 == n2,
+        (Edit::Update { old: o1, new: n1 }, Edit::Update { old: o2, new: n2 }) => {
+            o1 == o2 && n1.value() == n2.value()
+        }
+        (Edit::Rename { old: o1, new: n1 }, Edit::Rename { old: o2, new: n2 }) => {
+            o1 == o2 && n1.signature() == n2.signature()
+        }
+        (Edit::ReplaceRoot { new: n1, .. }, Edit::ReplaceRoot { new: n2, .. }) => {
+            n1.source() == n2.source()
+        }
+        (
+            Edit::Reorder {
+                old: o1, to: t1, ..
+            },
+            Edit::Reorder {
+                old: o2, to: t2, ..
+            },
+        ) => o1 == o2 && t1 == t2,
+        (
+            Edit::Move {
+                node: n1,
+                to_parent: p1,
+                ..
+            },
+            Edit::Move {
+                node: n2,
+                to_parent: p2,
+                ..
+            },
+        ) => n1 == n2 && p1 == p2,
+        _ => false,
+    }
+}
+
+/// Get the node of the base document on which two different edits conflict, if any.
+fn conflict<'a, 'base>(
+    e1: &Edit<'a, 'base, '_>,
+    e2: &Edit<'a, 'base, '_>,
+) -> Option<(XNode<'a, 'base>, ConflictKind)> {
+    match (e1, e2) {
+        (Edit::ReplaceRoot { old, .. }, _) | (_, Edit::ReplaceRoot { old, .. }) => {
+            Some((*old, ConflictKind::ReplaceRoot))
+        }
+        (Edit::Update { old: o1, .. }, Edit::Update { old: o2, .. }) if o1 == o2 => {
+            Some((*o1, ConflictKind::Update))
+        }
+        (Edit::Rename { old: o1, .. }, Edit::Rename { old: o2, .. }) if o1 == o2 => {
+            Some((*o1, ConflictKind::Rename))
+        }
+        (Edit::Move { node: n1, .. }, Edit::Move { node: n2, .. }) if n1 == n2 => {
+            Some((*n1, ConflictKind::Move))
+        }
+        (Edit::Reorder { old: o1, .. }, Edit::Reorder { old: o2, .. }) if o1 == o2 => {
+            Some((*o1, ConflictKind::Reorder))
+        }
+        // deleting a part of a deleted subtree does not conflict
+        (Edit::Delete(_), Edit::Delete(_)) => None,
+        (Edit::Delete(deleted), other)
+            if changed_nodes(other).iter().any(|n| n.is_within(*deleted)) =>
+        {
+            Some((*deleted, ConflictKind::DeletedByOurs))
+        }
+        (other, Edit::Delete(deleted))
+            if changed_nodes(other).iter().any(|n| n.is_within(*deleted)) =>
+        {
+            Some((*deleted, ConflictKind::DeletedByTheirs))
+        }
+        _ => None,
+    }
+}
+
+/// Get the text of an element of the base document after the changes of one side.
+fn version(diff: &Diff, element: XNode) -> String {
+    for e in diff {
+        match e {
+            Edit::ReplaceRoot { new, .. } => return new.source().to_string(),
+            Edit::Delete(node) if element.is_within(*node) => return String::new(),
+            _ => {}
+        }
+    }
+    diff.matched(element)
+        .map_or(element.source(), |node| node.source())
+        .to_string()
+}
+
+/// Get the range of the text of `element` to replace with both versions between conflict markers,
+/// and the replacement text. The markers start on a new line, like those of `git merge`.
+fn conflict_markers(
+    text: &str,
+    element: XNode,
+    ours: &str,
+    theirs: &str,
+) -> (std::ops::Range<usize>, String) {
+    let removal_range = element.removal_range();
+    let (range, indent, end) = if text[removal_range.start..].starts_with('\n') {
+        let indent = &text[removal_range.start + 1..element.range().start];
+        (removal_range, indent, "")
+    } else {
+        (element.range(), "", "\n")
+    };
+    let version = |version: &str| {
+        if version.is_empty() {
+            String::new()
+        } else {
+            format!("{indent}{version}\n")
+        }
+    };
+    let markers = format!(
+        "\n<<<<<<< ours\n{}=======\n{}>>>>>>> theirs{}",
+        version(ours),
+        version(theirs),
+        end
+    );
+    (range, markers)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::diff::{ChildOrder, diff};
+
+    const BASE: &str = r#"<config>
+    <server host="a" port="80"/>
+    <timeout>10</timeout>
+    <cache size="1"/>
+</config>"#;
+
+    fn merge_texts(ours: &str, theirs: &str) -> (String, Vec<(String, ConflictKind)>) {
+        let base = XTree::parse(BASE).unwrap();
+        let ours = XTree::parse(ours).unwrap();
+        let theirs = XTree::parse(theirs).unwrap();
+        let merge = merge(&base, &ours, &theirs);
+        let conflicts = merge
+            .conflicts
+            .iter()
+            .map(|c| (c.node.source().to_string(), c.kind))
+            .collect();
+        (merge.text, conflicts)
+    }
+
+    #[test]
+    fn test_merge_clean() {
+        let ours = r#"<config>
+    <timeout>10</timeout>
+    <server host="a" port="8080"/>
+    <cache size="1"/>
+    <log/>
+</config>"#;
+        let theirs = r#"<config>
+    <server host="a" port="80"/>
+    <timeout>20</timeout>
+</config>"#;
+        let (text, conflicts) = merge_texts(ours, theirs);
+        assert!(conflicts.is_empty());
+        assert_eq!(
+            text,
+            r#"<config>
+    <server host="a" port="8080"/>
+    <timeout>20</timeout>
+    <log/>
+</config>"#
+        );
+
+        // the changes of both sides are applied once
+        let (text, conflicts) = merge_texts(ours, ours);
+        assert!(conflicts.is_empty());
+        let merged = XTree::parse(&text).unwrap();
+        assert!(diff(&merged, &XTree::parse(ours).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn test_merge_update_conflict() {
+        let ours = BASE.replace("<timeout>10", "<timeout>20");
+        let theirs = BASE.replace("<timeout>10", "<timeout>30");
+        let (text, conflicts) = merge_texts(&ours, &theirs);
+        assert_eq!(conflicts, vec![("10".to_string(), ConflictKind::Update)]);
+        assert_eq!(
+            text,
+            r#"<config>
+    <server host="a" port="80"/>
+<<<<<<< ours
+    <timeout>20</timeout>
+=======
+    <timeout>30</timeout>
+>>>>>>> theirs
+    <cache size="1"/>
+</config>"#
+        );
+    }
+
+    #[test]
+    fn test_merge_delete_conflict() {
+        let ours = BASE.replace("\n    <server host=\"a\" port=\"80\"/>", "");
+        let theirs = BASE
+            .replace("port=\"80\"", "port=\"81\"")
+            .replace("size=\"1\"", "size=\"2\"");
+        let (text, conflicts) = merge_texts(&ours, &theirs);
+        assert_eq!(
+            conflicts,
+            vec![(
+                r#"<server host="a" port="80"/>"#.to_string(),
+                ConflictKind::DeletedByOurs
+            )]
+        );
+        assert_eq!(
+            text,
+            r#"<config>
+<<<<<<< ours
+=======
+    <server host="a" port="81"/>
+>>>>>>> theirs
+    <timeout>10</timeout>
+    <cache size="2"/>
+</config>"#
+        );
+    }
+
+    #[test]
+    fn test_merge_insert_positions() {
+        // each side inserts at a position of its own version
+        let base = XTree::parse("<r><x/><y/></r>").unwrap();
+        let ours = XTree::parse("<r><n1/><x/><y/></r>").unwrap();
+        let theirs = XTree::parse("<r><x/><y/><n2/></r>").unwrap();
+        let merge = merge(&base, &ours, &theirs);
+        assert!(merge.is_clean());
+        assert_eq!(merge.text, "<r><n1/><x/><y/><n2/></r>");
+
+        let theirs = XTree::parse("<r><y/><n2/><n3/></r>").unwrap();
+        let merge = super::merge(&base, &ours, &theirs);
+        assert!(merge.is_clean());
+        assert_eq!(merge.text, "<r><n1/><y/><n2/><n3/></r>");
+
+        // a reorder and an insertion
+        let base = XTree::parse("<r><x/><y/><z/></r>").unwrap();
+        let ours = XTree::parse("<r><z/><x/><y/></r>").unwrap();
+        let theirs = XTree::parse("<r><x/><y/><z/><n/></r>").unwrap();
+        let options = DiffOptions::default().with_child_order(ChildOrder::Ordered);
+        let merge = merge_with_options(&base, &ours, &theirs, options);
+        assert!(merge.is_clean());
+        assert_eq!(merge.text, "<r><z/><x/><y/><n/></r>");
+    }
+    #[test]
+    fn test_merge_nested_conflicts() {
+        let base = XTree::parse(r#"<r><a x="1"><b/></a><c/></r>"#).unwrap();
+        let ours = XTree::parse(r#"<r><a x="2"/><c><b/></c></r>"#).unwrap();
+        let theirs = XTree::parse(r#"<r><a x="3"><b/></a><c/></r>"#).unwrap();
+        let options = DiffOptions::default().with_move_detection(true);
+        let merge = merge_with_options(&base, &ours, &theirs, options);
+        assert_eq!(merge.conflicts.len(), 1);
+        // the move of `b` is part of the conflict on `a`
+        assert_eq!(
+            merge.text,
+            "<r>\n<<<<<<< ours\n<a x=\"2\"/>\n=======\n<a x=\"3\"><b/></a>\n>>>>>>> theirs\n<c/></r>"
+        );
+
+        // conflicts on nested elements are between the same markers
+        let base = XTree::parse(r#"<r><a x="1"><b y="1"/></a></r>"#).unwrap();
+        let ours = XTree::parse(r#"<r><a x="2"><b y="2"/></a></r>"#).unwrap();
+        let theirs = XTree::parse(r#"<r><a x="3"><b y="3"/></a></r>"#).unwrap();
+        let merge = super::merge(&base, &ours, &theirs);
+        assert_eq!(merge.conflicts.len(), 2);
+        assert_eq!(
+            merge.text,
+            "<r>\n<<<<<<< ours\n<a x=\"2\"><b y=\"2\"/></a>\n=======\n<a x=\"3\"><b y=\"3\"/></a>\n>>>>>>> theirs\n</r>"
+        );
+    }
+}
@@ -0,0 +1,415 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+};
+
+use crate::{
+    diff::Edit,
+    tree::{XNode, XTree},
+};
+
+/// Apply an edit script, calculated by [crate::diff::diff] from `tree` to another tree, to the text
+/// of `tree` and return the resulting XML document.
+///
+/// The text of `tree` is kept as is except for the changed parts, so the XML declaration, comments
+/// and formatting survive. Inserted nodes are copied from the text of the other tree, so the
+/// namespace prefixes they use must also be declared in `tree`.
+///
+/// Panics if two edits change the same part of the text, which a diff of `tree` never does.
+pub fn patch(tree: &XTree, diff: &[Edit]) -> String {
+    let mut patcher = Patcher::new(tree.text());
+    for edit in diff {
+        patcher.add(edit);
+    }
+    patcher.finish()
+}
+
+/// Replace the text in `range` with `text`.
+#[derive(Debug)]
+struct Splice {
+    range: Range<usize>,
+    text: String,
+    /// Whether the splice replaces a whole subtree, together with the changes inside it.
+    covering: bool,
+}
+
+/// Collect the changes to the text of a tree, and apply them all at once.
+pub(crate) struct Patcher<'a, 'doc> {
+    text: &'doc str,
+    splices: Vec<Splice>,
+    /// Ids of the children removed from their parent.
+    removed: HashSet<String>,
+    /// New children of each parent, by parent id: the parent, and the place and text of each child.
+    inserted: HashMap<String, (XNode<'a, 'doc>, Vec<(Place, String)>)>,
+    replaced_root: Option<Splice>,
+}
+
+/// Where a new child goes among the children of its parent.
+#[derive(Debug, Clone, Copy)]
+enum Place {
+    /// At this position among the children of the parent in the other tree.
+    Position(usize),
+    /// After this number of children of the parent in the original tree, not counting attributes.
+    After(usize),
+}
+
+impl<'a, 'doc: 'a> Patcher<'a, 'doc> {
+    pub(crate) fn new(text: &'doc str) -> Self {
+        Self {
+            text,
+            splices: Vec::new(),
+            removed: HashSet::new(),
+            inserted: HashMap::new(),
+            replaced_root: None,
+        }
+    }
+
+    /// Add the change of an edit, calculated from the tree of this patcher to any other tree.
+    pub(crate) fn add(&mut self, edit: &Edit<'a, 'doc, '_>) {
+        self.add_at(edit, None);
+    }
+
+    /// Same as [Self::add], but the node inserted, reordered or moved by the edit goes after `after`
+    /// children of its new parent in the original tree, not counting attributes, instead of at its
+    /// position in the other tree.
+    pub(crate) fn add_after(&mut self, edit: &Edit<'a, 'doc, '_>, after: usize) {
+        self.add_at(edit, Some(after));
+    }
+
+    fn add_at(&mut self, edit: &Edit<'a, 'doc, '_>, after: Option<usize>) {
+        let place = |position| after.map_or(Place::Position(position), Place::After);
+        match edit {
+            Edit::Insert {
+                child_node,
+                to_node,
+                position,
+            } => self.insert(*to_node, *child_node, place(*position)),
+            Edit::Delete(node) => self.remove(*node),
+            Edit::Update { old, new } => {
+                let text = if old.is_attribute() {
+                    let quote = self.text[old.value_range().end..].chars().next().unwrap();
+                    escape_attribute_value(new.value().unwrap(), quote)
+                } else {
+                    new.source().to_string()
+                };
+                self.replace(old.value_range(), text);
+            }
+            Edit::ReplaceRoot { old, new } => {
+                self.replaced_root = Some(Splice {
+                    range: old.range(),
+                    text: new.source().to_string(),
+                    covering: true,
+                });
+            }
+            Edit::Reorder { old, to, .. } => {
+                self.remove(*old);
+                self.insert(old.parent().unwrap(), *old, place(*to));
+            }
+            Edit::Move {
+                node,
+                to_parent,
+                position,
+                ..
+            } => {
+                self.remove(*node);
+                self.insert(*to_parent, *node, place(*position));
+            }
+            Edit::Rename { old, new } => {
+                let (start, end) = old.qname_ranges();
+                self.replace(start, new.qname().to_string());
+                if let Some(end) = end {
+                    self.replace(end, new.qname().to_string());
+                }
+            }
+        }
+    }
+
+    /// Replace the text in `range` of the original text.
+    pub(crate) fn replace(&mut self, range: Range<usize>, text: String) {
+        self.splices.push(Splice {
+            range,
+            text,
+            covering: false,
+        });
+    }
+
+    /// Replace the text in `range` of the original text, discarding the other changes inside it.
+    pub(crate) fn replace_covering(&mut self, range: Range<usize>, text: String) {
+        self.splices.push(Splice {
+            range,
+            text,
+            covering: true,
+        });
+    }
+
+    fn remove(&mut self, node: XNode) {
+        self.removed.insert(node.id().to_string());
+        self.replace(node.removal_range(), String::new());
+    }
+
+    fn insert(&mut self, parent: XNode<'a, 'doc>, child: XNode, place: Place) {
+        if child.is_attribute() {
+            let tag_end = parent.start_tag_end();
+            let offset = self.text[..tag_end].trim_end_matches('/').trim_end().len();
+            self.replace(offset..offset, format!(" {}", child.source()));
+        } else {
+            self.inserted
+                .entry(parent.id().to_string())
+                .or_insert_with(|| (parent, Vec::new()))
+                .1
+                .push((place, child.source().to_string()));
+        }
+    }
+
+    /// Place the new children among the remaining ones, so that each one ends up at its place.
+    fn place_inserted_children(&mut self) {
+        for (parent, children) in std::mem::take(&mut self.inserted).into_values() {
+            // the indices of the remaining children among the children of the original tree
+            let remaining: Vec<_> = parent
+                .children()
+                .into_iter()
+                .filter(|child| !child.is_attribute())
+                .enumerate()
+                .filter(|(_, child)| !self.removed.contains(&child.id().to_string()))
+                .collect();
+            let indent = parent.child_indent();
+
+            // the number of remaining children before each new child
+            let mut slots = Vec::new();
+            let mut positioned: Vec<_> = children
+                .iter()
+                .filter_map(|(place, text)| match place {
+                    Place::Position(position) => Some((*position, text)),
+                    Place::After(_) => None,
+                })
+                .collect();
+            positioned.sort_by_key(|(position, _)| *position);
+            let (mut slot, mut index) = (0, 0);
+            for (position, text) in positioned {
+                while position > index && slot < remaining.len() {
+                    slot += 1;
+                    index += 1;
+                }
+                slots.push((slot, text));
+                index += 1;
+            }
+            for (place, text) in &children {
+                if let Place::After(after) = place {
+                    let slot = remaining.iter().take_while(|(i, _)| i < after).count();
+                    slots.push((slot, text));
+                }
+            }
+            slots.sort_by_key(|(slot, _)| *slot);
+
+            let mut appended = Vec::new();
+            for (slot, text) in slots {
+                match remaining.get(slot) {
+                    Some((_, next)) => {
+                        // before the line break preceding `next`, if any
+                        let offset = next.removal_range().start;
+                        let text = format!("{}{}", indent, text);
+                        self.replace(offset..offset, text);
+                    }
+                    None => appended.push(text.clone()),
+                }
+            }
+            if appended.is_empty() {
+                continue;
+            }
+            match parent.children_end() {
+                Some(offset) => {
+                    let text: String = appended
+                        .iter()
+                        .map(|child| format!("{}{}", indent, child))
+                        .collect();
+                    self.replace(offset..offset, text);
+                }
+                None => {
+                    // `<a/>` becomes `<a>...</a>`
+                    let tag_end = parent.start_tag_end();
+                    let text = format!(">{}</{}>", appended.concat(), parent.qname());
+                    self.replace(tag_end - 1..tag_end + 1, text);
+                }
+            }
+        }
+    }
+
+    /// Apply all the changes and return the new text.
+    pub(crate) fn finish(mut self) -> String {
+        if let Some(splice) = self.replaced_root.take() {
+            self.splices = vec![splice];
+        } else {
+            self.place_inserted_children();
+        }
+        let covered: Vec<_> = self
+            .splices
+            .iter()
+            .filter(|splice| splice.covering)
+            .map(|splice| splice.range.clone())
+            .collect();
+        self.splices.retain(|splice| {
+            splice.covering
+                || !covered
+                    .iter()
+                    .any(|range| range.start <= splice.range.start && splice.range.end <= range.end)
+        });
+        // insertions at a position go before a replacement starting at the same position
+        self.splices
+            .sort_by_key(|splice| (splice.range.start, splice.range.end));
+        let mut result = String::with_capacity(self.text.len());
+        let mut cursor = 0;
+        for splice in self.splices {
+            // the edits of a diff never change the same text twice, dropping one would silently
+            // return a wrong document
+            assert!(
+                splice.range.start >= cursor,
+                "overlapping change {splice:?}"
+            );
+            result.push_str(&self.text[cursor..splice.range.start]);
+            result.push_str(&splice.text);
+            cursor = splice.range.end;
+        }
+        result.push_str(&self.text[cursor..]);
+        result
+    }
+}
+
+fn escape_attribute_value(value: &str, quote: char) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '"' if quote == '"' => escaped.push_str("&quot;"),
+            '\'' if quote == '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::*;
+    use crate::diff::{ChildOrder, DiffOptions, diff, diff_with_options};
+
+    fn assert_round_trip(text1: &str, text2: &str, options: DiffOptions) {
+        let tree1 = XTree::parse(text1).unwrap();
+        let tree2 = XTree::parse(text2).unwrap();
+        let edits = diff_with_options(&tree1, &tree2, options.clone());
+        let patched = patch(&tree1, &edits);
+        let patched_tree = XTree::parse(&patched).unwrap();
+        let remaining = diff_with_options(&patched_tree, &tree2, options);
+        assert!(
+            remaining.is_empty(),
+            "patched text:\n{patched}\nremaining edits: {:?}",
+            remaining.iter().map(|e| e.to_string()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_patch_round_trip() {
+        let text1 = fs::read_to_string("test/file1.xml").unwrap();
+        let text2 = fs::read_to_string("test/file2.xml").unwrap();
+        assert_round_trip(&text1, &text2, DiffOptions::default());
+        assert_round_trip(&text2, &text1, DiffOptions::default());
+    }
+
+    #[test]
+    fn test_patch_round_trip_with_options() {
+        let text1 =
+            r#"<r><a k="1"><b/><c>x</c></a><d>1</d><e/><Phone><n>1</n><m>2</m></Phone></r>"#;
+        let text2 = r#"<r><d>2</d><a k='2"'><c>y</c><b>new</b></a><e><c>x</c></e><Telephone><n>1</n><m>2</m></Telephone></r>"#;
+        assert_round_trip(text1, text2, DiffOptions::default());
+        assert_round_trip(
+            text1,
+            text2,
+            DiffOptions::default()
+                .with_move_detection(true)
+                .with_rename_detection(0.5),
+        );
+        assert_round_trip(
+            text1,
+            text2,
+            DiffOptions::default().with_child_order(ChildOrder::Ordered),
+        );
+        assert_round_trip(
+            "<a>1</a>",
+            "<b>2</b>",
+            DiffOptions::default().with_replaced_root_diff(true),
+        );
+    }
+
+    #[test]
+    fn test_patch_keeps_formatting() {
+        let text1 = r#"<?xml version="1.0"?>
+<!-- list of items -->
+<List>
+    <Item id="1">a</Item>
+    <Item id="2">b</Item>
+</List>"#;
+        let text2 = r#"<List><Item id="1">a</Item><Item id="3">c</Item></List>"#;
+        let tree1 = XTree::parse(text1).unwrap();
+        let tree2 = XTree::parse(text2).unwrap();
+        let patched = patch(&tree1, &diff(&tree1, &tree2));
+        assert_eq!(
+            patched,
+            r#"<?xml version="1.0"?>
+<!-- list of items -->
+<List>
+    <Item id="1">a</Item>
+    <Item id="3">c</Item>
+</List>"#
+        );
+    }
+
+    #[test]
+    fn test_patch_start_tag() {
+        // the last item of the start tag is a namespace declaration whose value contains `>`
+        let text1 = r#"<a x="1" xmlns:p='u>v'/>"#;
+        let text2 = r#"<a x="1" y="2" xmlns:p="u>v"><b/></a>"#;
+        let tree1 = XTree::parse(text1).unwrap();
+        let tree2 = XTree::parse(text2).unwrap();
+        let patched = patch(&tree1, &diff(&tree1, &tree2));
+        assert_eq!(patched, r#"<a x="1" xmlns:p='u>v' y="2"><b/></a>"#);
+    }
+
+    #[test]
+    #[should_panic(expected = "overlapping change")]
+    fn test_patch_overlapping_changes() {
+        let mut patcher = Patcher::new("<a><b/></a>");
+        patcher.replace(3..7, String::new());
+        patcher.replace(4..5, "c".to_string());
+        patcher.finish();
+    }
+
+    #[test]
+    fn test_patch_covering_change() {
+        let mut patcher = Patcher::new("<a><b/></a>");
+        patcher.replace(4..5, "c".to_string());
+        patcher.replace_covering(3..7, "<d/>".to_string());
+        assert_eq!(patcher.finish(), "<a><d/></a>");
+    }
+
+    #[test]
+    fn test_patch_insert_position() {
+        let text1 = "<List>\n  <Item>a</Item>\n  <Item>d</Item>\n</List>";
+        let text2 = "<List><Item>0</Item><Item>a</Item><Item>b</Item><Item>c</Item><Item>d</Item><Item>e</Item></List>";
+        let tree1 = XTree::parse(text1).unwrap();
+        let tree2 = XTree::parse(text2).unwrap();
+        let patched = patch(
+            &tree1,
+            &diff_with_options(
+                &tree1,
+                &tree2,
+                DiffOptions::default().with_child_order(ChildOrder::Ordered),
+            ),
+        );
+        assert_eq!(
+            patched,
+            "<List>\n  <Item>0</Item>\n  <Item>a</Item>\n  <Item>b</Item>\n  <Item>c</Item>\n  <Item>d</Item>\n  <Item>e</Item>\n</List>"
+        );
+    }
+}
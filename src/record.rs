@@ -0,0 +1,290 @@
+use std::ops::Range;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    diff::{Diff, Edit},
+    tree::{XNode, XNodeName, XTree},
+};
+
+/// Kind of an [Edit].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum EditKind {
+    Insert,
+    Delete,
+    Update,
+    ReplaceRoot,
+    Reorder,
+    Move,
+    Rename,
+}
+
+/// Kind of an [XNode].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum NodeKind {
+    Element,
+    Attribute,
+    Text,
+}
+
+/// Owned description of a node, which does not borrow its tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct NodeRecord {
+    /// Positions of the node and its ancestors among their siblings, from the child of the root down
+    /// to the node, see [XNode::position]. Empty for the root.
+    pub path: Vec<usize>,
+    /// Absolute location of the node, see [XNode::xpath].
+    pub xpath: String,
+    pub kind: NodeKind,
+    /// Local name of an element or attribute.
+    pub name: Option<String>,
+    pub namespace: Option<String>,
+    /// Value of an attribute, or trimmed value of a text, as compared by the diff.
+    pub value: Option<String>,
+    /// Byte range of the node in the text of its document.
+    pub range: Range<usize>,
+}
+
+/// Owned description of an [Edit], which does not borrow the trees.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EditRecord {
+    pub kind: EditKind,
+    /// Node of `tree1`: the deleted, updated, replaced, reordered, moved or renamed node.
+    pub old: Option<NodeRecord>,
+    /// Node of `tree2`: the inserted node, or the node after the update, replacement, reordering,
+    /// move or renaming.
+    pub new: Option<NodeRecord>,
+    /// Node of `tree1` under which the node is inserted or moved.
+    pub parent: Option<NodeRecord>,
+    /// Node of `tree1` from which the node is moved.
+    pub from_parent: Option<NodeRecord>,
+    /// Position of the inserted, reordered or moved node among the children of its new parent.
+    pub position: Option<usize>,
+}
+
+impl From<XNode<'_, '_>> for NodeRecord {
+    fn from(node: XNode<'_, '_>) -> Self {
+        let mut path = Vec::new();
+        let mut current = node;
+        while let Some(parent) = current.parent() {
+            path.push(current.position());
+            current = parent;
+        }
+        path.reverse();
+
+        let (kind, name, namespace) = match node.name() {
+            XNodeName::TagName(name) => (
+                NodeKind::Element,
+                Some(name.name().to_string()),
+                name.namespace().map(str::to_string),
+            ),
+            XNodeName::AttributeName(attribute) => (
+                NodeKind::Attribute,
+                Some(attribute.name().to_string()),
+                attribute.namespace().map(str::to_string),
+            ),
+            XNodeName::Text => (NodeKind::Text, None, None),
+        };
+        Self {
+            path,
+            xpath: node.xpath(),
+            kind,
+            name,
+            namespace,
+            value: node.compared_value().map(str::to_string),
+            range: node.range(),
+        }
+    }
+}
+
+impl EditRecord {
+    /// Describe the insertion of a whole document, for a missing first document.
+    pub fn insert_document(tree: &XTree) -> Self {
+        EditRecord {
+            kind: EditKind::Insert,
+            old: None,
+            new: Some(tree.root().into()),
+            parent: None,
+            from_parent: None,
+            position: Some(0),
+        }
+    }
+
+    /// Describe the deletion of a whole document, for a missing second document.
+    pub fn delete_document(tree: &XTree) -> Self {
+        EditRecord {
+            kind: EditKind::Delete,
+            old: Some(tree.root().into()),
+            new: None,
+            parent: None,
+            from_parent: None,
+            position: None,
+        }
+    }
+
+    /// Describe an edit of a diff. The diff gives the node of `tree2` of a move.
+    fn new(diff: &Diff, edit: &Edit) -> Self {
+        let record = |kind, old: Option<XNode>, new: Option<XNode>| EditRecord {
+            kind,
+            old: old.map(NodeRecord::from),
+            new: new.map(NodeRecord::from),
+            parent: None,
+            from_parent: None,
+            position: None,
+        };
+        match *edit {
+            Edit::Insert {
+                child_node,
+                to_node,
+                position,
+            } => EditRecord {
+                parent: Some(to_node.into()),
+                position: Some(position),
+                ..record(EditKind::Insert, None, Some(child_node))
+            },
+            Edit::Delete(node) => record(EditKind::Delete, Some(node), None),
+            Edit::Update { old, new } => record(EditKind::Update, Some(old), Some(new)),
+            Edit::ReplaceRoot { old, new } => record(EditKind::ReplaceRoot, Some(old), Some(new)),
+            Edit::Reorder { old, new, to, .. } => EditRecord {
+                position: Some(to),
+                ..record(EditKind::Reorder, Some(old), Some(new))
+            },
+            Edit::Move {
+                node,
+                from_parent,
+                to_parent,
+                position,
+            } => EditRecord {
+                parent: Some(to_parent.into()),
+                from_parent: Some(from_parent.into()),
+                position: Some(position),
+                ..record(EditKind::Move, Some(node), diff.matched(node))
+            },
+            Edit::Rename { old, new } => record(EditKind::Rename, Some(old), Some(new)),
+        }
+    }
+}
+
+impl Diff<'_, '_, '_> {
+    /// Get owned descriptions of the edits.
+    pub fn to_records(&self) -> Vec<EditRecord> {
+        self.iter()
+            .map(|edit| EditRecord::new(self, edit))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::diff::{DiffOptions, diff, diff_with_options};
+
+    #[test]
+    fn test_edit_records() {
+        let tree1 = XTree::parse(r#"<a xmlns:n="urn:n"><b n:x="1"/><c>text</c></a>"#).unwrap();
+        let tree2 = XTree::parse(r#"<a xmlns:n="urn:n"><b n:x="2"/><c>text</c><d/></a>"#).unwrap();
+        let mut records = diff(&tree1, &tree2).to_records();
+        records.sort_by_key(|r| r.kind == EditKind::Insert);
+        assert_eq!(
+            records,
+            vec![
+                EditRecord {
+                    kind: EditKind::Update,
+                    old: Some(NodeRecord {
+                        path: vec![0, 0],
+                        xpath: "/a/b/@n:x".to_string(),
+                        kind: NodeKind::Attribute,
+                        name: Some("x".to_string()),
+                        namespace: Some("urn:n".to_string()),
+                        value: Some("1".to_string()),
+                        range: 22..29,
+                    }),
+                    new: Some(NodeRecord {
+                        path: vec![0, 0],
+                        xpath: "/a/b/@n:x".to_string(),
+                        kind: NodeKind::Attribute,
+                        name: Some("x".to_string()),
+                        namespace: Some("urn:n".to_string()),
+                        value: Some("2".to_string()),
+                        range: 22..29,
+                    }),
+                    parent: None,
+                    from_parent: None,
+                    position: None,
+                },
+                EditRecord {
+                    kind: EditKind::Insert,
+                    old: None,
+                    new: Some(NodeRecord {
+                        path: vec![2],
+                        xpath: "/a/d".to_string(),
+                        kind: NodeKind::Element,
+                        name: Some("d".to_string()),
+                        namespace: None,
+                        value: None,
+                        range: 42..46,
+                    }),
+                    parent: Some(NodeRecord {
+                        path: vec![],
+                        xpath: "/a".to_string(),
+                        kind: NodeKind::Element,
+                        name: Some("a".to_string()),
+                        namespace: None,
+                        value: None,
+                        range: 0..46,
+                    }),
+                    from_parent: None,
+                    position: Some(2),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_edit_records_move() {
+        let tree1 = XTree::parse("<a><b><c/></b><d/></a>").unwrap();
+        let tree2 = XTree::parse("<a><b/><d><e/><c/></d></a>").unwrap();
+        let options = DiffOptions::default().with_move_detection(true);
+        let records = diff_with_options(&tree1, &tree2, options).to_records();
+        let record = records.iter().find(|r| r.kind == EditKind::Move).unwrap();
+        let path = |node: &Option<NodeRecord>| node.as_ref().unwrap().path.clone();
+        assert_eq!(path(&record.old), [0, 0]);
+        assert_eq!(path(&record.new), [1, 1]);
+        assert_eq!(record.new.as_ref().unwrap().range, 14..18);
+        assert_eq!(path(&record.from_parent), [0]);
+        assert_eq!(path(&record.parent), [1]);
+        assert_eq!(record.position, Some(1));
+    }
+
+    #[test]
+    fn test_edit_records_element_value() {
+        // an element has no value, even with a text child
+        let tree1 = XTree::parse("<a/>").unwrap();
+        let tree2 = XTree::parse("<a><Status>Single</Status></a>").unwrap();
+        let records = diff(&tree1, &tree2).to_records();
+        let new = records[0].new.as_ref().unwrap();
+        assert_eq!(new.kind, NodeKind::Element);
+        assert_eq!(new.value, None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_edit_records_json() {
+        let text1 = std::fs::read_to_string("test/file1.xml").unwrap();
+        let text2 = std::fs::read_to_string("test/file2.xml").unwrap();
+        let tree1 = XTree::parse(&text1).unwrap();
+        let tree2 = XTree::parse(&text2).unwrap();
+        let records = diff(&tree1, &tree2).to_records();
+        let json = serde_json::to_string(&records).unwrap();
+        assert!(json.contains(r#""kind":"update""#));
+        let parsed: Vec<EditRecord> = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, records);
+    }
+}
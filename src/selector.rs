@@ -0,0 +1,147 @@
+use crate::{
+    tree::{XNode, XNodeName},
+    xpath::XPath,
+};
+
+/// Select nodes of a tree, to apply per-element diff options or to ignore nodes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Selector {
+    /// Elements with this local name, in any namespace.
+    Name(String),
+    /// Elements with this local name, in this namespace.
+    QName { namespace: String, name: String },
+    /// Elements at this absolute path of local names, e.g. `/Profile/Customer/Steps`.
+    /// A `*` step matches any name.
+    Path(String),
+    /// Attributes with this local name, in any namespace.
+    Attribute(String),
+    /// Nodes selected by an XPath expression. It is evaluated on the whole tree, so prefer the
+    /// other selectors for large trees.
+    XPath(XPath),
+    /// Nodes whose path of local names matches a glob pattern, e.g. `/Profile/**/@Timestamp` or
+    /// `**/Id`. Attributes are `@name` steps and texts are `text()` steps. In a step, `*` matches
+    /// any characters and `?` any single character, and a `**` step matches any number of steps.
+    Glob(String),
+}
+
+impl Selector {
+    /// Check whether the node is selected.
+    pub fn matches(&self, node: XNode) -> bool {
+        match (self, node.name()) {
+            (Selector::Name(name), XNodeName::TagName(tag_name)) => tag_name.name() == name,
+            (Selector::QName { namespace, name }, XNodeName::TagName(tag_name)) => {
+                tag_name.name() == name && tag_name.namespace() == Some(namespace.as_str())
+            }
+            (Selector::Path(path), XNodeName::TagName(_)) => {
+                let Some(path) = path.strip_prefix('/') else {
+                    return false;
+                };
+                let steps: Vec<_> = path.split('/').collect();
+                let mut names = Vec::new();
+                let mut current = Some(node);
+                while let Some(n) = current {
+                    if let XNodeName::TagName(tag_name) = n.name() {
+                        names.push(tag_name.name().to_string());
+                    }
+                    current = n.parent();
+                }
+                names.reverse();
+                names.len() == steps.len()
+                    && steps
+                        .iter()
+                        .zip(&names)
+                        .all(|(step, name)| *step == "*" || step == name)
+            }
+            (Selector::Attribute(name), XNodeName::AttributeName(attribute)) => {
+                attribute.name() == name
+            }
+            (Selector::XPath(xpath), _) => xpath.matches(node),
+            (Selector::Glob(pattern), _) => {
+                let mut steps = Vec::new();
+                let mut current = Some(node);
+                while let Some(n) = current {
+                    steps.push(match n.name() {
+                        XNodeName::TagName(tag_name) => tag_name.name().to_string(),
+                        XNodeName::AttributeName(attribute) => format!("@{}", attribute.name()),
+                        XNodeName::Text => "text()".to_string(),
+                    });
+                    current = n.parent();
+                }
+                steps.reverse();
+                let pattern: Vec<_> = pattern.trim_start_matches('/').split('/').collect();
+                glob_steps(&pattern, &steps)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Match the steps of a path against the steps of a glob pattern.
+fn glob_steps(pattern: &[&str], steps: &[String]) -> bool {
+    match pattern.split_first() {
+        None => steps.is_empty(),
+        Some((&"**", rest)) => (0..=steps.len()).any(|i| glob_steps(rest, &steps[i..])),
+        Some((step_pattern, rest)) => steps.split_first().is_some_and(|(step, steps)| {
+            let step_pattern: Vec<_> = step_pattern.chars().collect();
+            let step: Vec<_> = step.chars().collect();
+            glob(&step_pattern, &step) && glob_steps(rest, steps)
+        }),
+    }
+}
+
+/// Match a step against a step pattern with `*` and `?` wildcards.
+fn glob(pattern: &[char], text: &[char]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some(('*', rest)) => (0..=text.len()).any(|i| glob(rest, &text[i..])),
+        Some(('?', rest)) => !text.is_empty() && glob(rest, &text[1..]),
+        Some((c, rest)) => text.first() == Some(c) && glob(rest, &text[1..]),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tree::XTree;
+
+    #[test]
+    fn test_selector_matches() {
+        let tree = XTree::parse(r#"<a xmlns:n="urn:n"><n:b x="1"><c/></n:b></a>"#).unwrap();
+        let b = tree.root().children()[0];
+        let c = b.children()[0];
+        let x = b.children()[1];
+
+        assert!(Selector::Name("b".to_string()).matches(b));
+        assert!(!Selector::Name("x".to_string()).matches(x));
+        assert!(
+            Selector::QName {
+                namespace: "urn:n".to_string(),
+                name: "b".to_string()
+            }
+            .matches(b)
+        );
+        assert!(
+            !Selector::QName {
+                namespace: "urn:other".to_string(),
+                name: "b".to_string()
+            }
+            .matches(b)
+        );
+        assert!(Selector::Path("/a/b/c".to_string()).matches(c));
+        assert!(Selector::Path("/a/*/c".to_string()).matches(c));
+        assert!(!Selector::Path("/a/c".to_string()).matches(c));
+        assert!(!Selector::Path("a/b/c".to_string()).matches(c));
+
+        assert!(Selector::Attribute("x".to_string()).matches(x));
+        assert!(!Selector::Attribute("b".to_string()).matches(b));
+        assert!(Selector::XPath(XPath::parse("//n:b/@x").unwrap()).matches(x));
+        assert!(!Selector::XPath(XPath::parse("//c/@x").unwrap()).matches(x));
+        assert!(Selector::Glob("/a/b/@x".to_string()).matches(x));
+        assert!(Selector::Glob("**/@?".to_string()).matches(x));
+        assert!(Selector::Glob("/a/**/c".to_string()).matches(c));
+        assert!(Selector::Glob("**/c".to_string()).matches(c));
+        assert!(Selector::Glob("/*/b*".to_string()).matches(b));
+        assert!(!Selector::Glob("/a/c".to_string()).matches(c));
+        assert!(!Selector::Glob("**/b".to_string()).matches(c));
+    }
+}
@@ -2,11 +2,27 @@ use md5::Digest;
 use roxmltree::{Attribute, Document, ExpandedName, Node, NodeId};
 use std::{borrow::Cow, fmt::Display, hash::Hash};
 
+use crate::xpath::XPath;
+
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum XTreeError {
     ParseError(roxmltree::Error),
+    /// An XPath expression is invalid or not supported, see [crate::xpath::XPath].
+    InvalidXPath(String),
+}
+
+impl Display for XTreeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            XTreeError::ParseError(e) => write!(f, "{e}"),
+            XTreeError::InvalidXPath(e) => write!(f, "invalid XPath: {e}"),
+        }
+    }
 }
 
+impl std::error::Error for XTreeError {}
+
 /// A tree representation of the XML format. It is a wrapper around [roxmltree::Document]
 #[derive(Debug)]
 pub struct XTree<'doc>(Document<'doc>);
@@ -77,7 +93,7 @@ impl<'a, 'doc: 'a> XNode<'a, 'doc> {
     }
 
     /// Get node name.
-    pub fn name(&self) -> XNodeName {
+    pub fn name(&self) -> XNodeName<'_, '_> {
         if let Some(attr) = self.attr {
             XNodeName::AttributeName(attr)
         } else if self.is_text() {
@@ -123,6 +139,37 @@ impl<'a, 'doc: 'a> XNode<'a, 'doc> {
         nodes.chain(attrs).collect()
     }
 
+    /// Get the element and text children, including the whitespace-only texts, without the
+    /// attributes.
+    pub(crate) fn child_nodes(&self) -> Vec<Self> {
+        if self.attr.is_some() {
+            return Vec::new();
+        }
+        self.node
+            .children()
+            .filter(|node| node.is_element() || node.is_text())
+            .map(|node| Self { node, attr: None })
+            .collect()
+    }
+
+    /// Get the namespace URI bound to a prefix, or the default namespace for `None`, in the scope of
+    /// the element, or of the element of an attribute.
+    pub(crate) fn lookup_namespace_uri(&self, prefix: Option<&str>) -> Option<&'a str> {
+        self.node.lookup_namespace_uri(prefix)
+    }
+
+    /// Get the position of the node among the children of its parent, see [Self::children].
+    /// The root node is at position 0.
+    pub fn position(&self) -> usize {
+        self.parent().map_or(0, |parent| {
+            parent
+                .children()
+                .iter()
+                .position(|child| child == self)
+                .unwrap()
+        })
+    }
+
     pub fn is_attribute(&self) -> bool {
         self.attr.is_some()
     }
@@ -144,6 +191,16 @@ impl<'a, 'doc: 'a> XNode<'a, 'doc> {
         }
     }
 
+    /// Get the value as compared by the diff: the value of an attribute, or the trimmed text. An
+    /// element has no value.
+    pub(crate) fn compared_value(&self) -> Option<&str> {
+        match self.attr {
+            Some(attr) => Some(attr.value()),
+            None if self.node.is_text() => self.node.text().map(str::trim),
+            None => None,
+        }
+    }
+
     /// Get the byte range of this node from the original text.
     pub fn range(&self) -> core::ops::Range<usize> {
         if let Some(attr) = self.attr {
@@ -153,30 +210,279 @@ impl<'a, 'doc: 'a> XNode<'a, 'doc> {
         }
     }
 
-    pub(crate) fn hash(&self) -> Digest {
+    /// Get the text of this node from the original document, i.e. the text in [Self::range].
+    pub fn source(&self) -> &'doc str {
+        &self.node.document().input_text()[self.range()]
+    }
+
+    /// Get the byte range of the raw value of an attribute or a text node, without quotes.
+    pub(crate) fn value_range(&self) -> core::ops::Range<usize> {
         if let Some(attr) = self.attr {
-            md5::compute(format!(
-                "{}{}={}",
-                attr.namespace().unwrap_or_default(),
-                attr.name(),
-                attr.value()
-            ))
+            attr.range_value()
         } else {
-            match self.node.node_type() {
-                roxmltree::NodeType::Element => {
-                    let name = self.node.tag_name().name();
-                    let namespace = self.node.tag_name().namespace().unwrap_or_default();
-                    md5::compute(format!("{}:{}", namespace, name))
-                }
-                roxmltree::NodeType::Text => {
-                    md5::compute(self.node.text().unwrap_or_default().trim())
+            self.node.range()
+        }
+    }
+
+    /// Get the byte range to remove from the original text to delete this node. It includes the
+    /// whitespace separating the node from the previous one, so that no blank line is left.
+    pub(crate) fn removal_range(&self) -> core::ops::Range<usize> {
+        let range = self.range();
+        let text = self.node.document().input_text();
+        if self.attr.is_some() {
+            return text[..range.start].trim_end().len()..range.end;
+        }
+        match self.node.prev_sibling() {
+            Some(prev) if prev.is_text() && prev.text().unwrap().trim().is_empty() => {
+                let whitespace = &text[prev.range()];
+                prev.range().start + whitespace.rfind('\n').unwrap_or(0)..range.end
+            }
+            _ => range,
+        }
+    }
+
+    /// Get the whitespace before the first child of an element, used to indent new children.
+    pub(crate) fn child_indent(&self) -> &'doc str {
+        match self.node.first_child() {
+            Some(child) if child.is_text() && child.text().unwrap().trim().is_empty() => {
+                &self.node.document().input_text()[child.range()]
+            }
+            _ => "",
+        }
+    }
+
+    /// Get the byte position of the `>` closing the start tag of an element. The quoted values of
+    /// the attributes and namespace declarations may contain `>`.
+    pub(crate) fn start_tag_end(&self) -> usize {
+        let text = self.node.document().input_text();
+        let start = self.node.range().start;
+        let mut quote = None;
+        for (i, c) in text[start..].char_indices() {
+            match (quote, c) {
+                (None, '"' | '\'') => quote = Some(c),
+                (Some(q), _) if c == q => quote = None,
+                (None, '>') => return start + i,
+                _ => {}
+            }
+        }
+        unreachable!("unclosed start tag")
+    }
+
+    /// Get the byte position where new children can be appended to an element: after its last child,
+    /// before the whitespace preceding the end tag. `None` for an empty element tag like `<a/>`.
+    pub(crate) fn children_end(&self) -> Option<usize> {
+        let text = self.node.document().input_text();
+        let tag_end = self.start_tag_end();
+        if text[..tag_end].ends_with('/') {
+            return None;
+        }
+        match self.node.last_child() {
+            Some(child) if child.is_text() && child.text().unwrap().trim().is_empty() => {
+                Some(child.range().start)
+            }
+            Some(child) => Some(child.range().end),
+            None => Some(tag_end + 1),
+        }
+    }
+
+    /// Get the byte ranges of the qualified name in the start tag and in the end tag of an element.
+    pub(crate) fn qname_ranges(
+        &self,
+    ) -> (core::ops::Range<usize>, Option<core::ops::Range<usize>>) {
+        let text = self.node.document().input_text();
+        let range = self.node.range();
+        let qname_len = |from: usize| {
+            text[from..]
+                .find(|c: char| c.is_whitespace() || c == '/' || c == '>')
+                .unwrap()
+        };
+        let start = range.start + 1;
+        let start_qname = start..start + qname_len(start);
+        if text[..self.start_tag_end()].ends_with('/') {
+            return (start_qname, None);
+        }
+        let end = range.start + text[range.clone()].rfind("</").unwrap() + 2;
+        (start_qname, Some(end..end + qname_len(end)))
+    }
+
+    /// Check whether the node is `ancestor` or one of its descendants.
+    pub(crate) fn is_within(&self, ancestor: Self) -> bool {
+        let mut current = Some(*self);
+        while let Some(n) = current {
+            if n == ancestor {
+                return true;
+            }
+            current = n.parent();
+        }
+        false
+    }
+
+    /// Get the namespaces in scope of the node, as prefix and URI pairs.
+    pub(crate) fn namespaces(&self) -> Vec<(Option<&'a str>, &'a str)> {
+        let element = if self.node.is_text() {
+            self.node.parent().unwrap()
+        } else {
+            self.node
+        };
+        element
+            .namespaces()
+            .map(|ns| (ns.name(), ns.uri()))
+            .collect()
+    }
+
+    /// Get the qualified name of an element or attribute, as written in the original text.
+    pub(crate) fn qname(&self) -> &'doc str {
+        let text = self.node.document().input_text();
+        match self.attr {
+            Some(attr) => &text[attr.range_qname()],
+            None => &text[self.qname_ranges().0],
+        }
+    }
+
+    /// Get an absolute XPath locating the node, such as `/Profile/Customer/Address[2]/@Type`. Unlike
+    /// [Self::id], it is stable across parses of the same text. The names are qualified as in the
+    /// original text, and a step has a position predicate if siblings share its name.
+    pub fn xpath(&self) -> String {
+        self.xpath_steps(false, &|_| true, &|_| None::<Self>)
+    }
+
+    /// Same as [Self::xpath], in a modified version of the document: only the elements and texts
+    /// for which `kept` is true remain, and the elements for which `renamed` gives an element of
+    /// another document have its name. All the steps below the root have a position predicate, so
+    /// that the XPath still locates the node once siblings are added after it.
+    pub(crate) fn xpath_with<'b, 'doc2: 'b>(
+        &self,
+        kept: &dyn Fn(XNode<'a, 'doc>) -> bool,
+        renamed: &dyn Fn(XNode<'a, 'doc>) -> Option<XNode<'b, 'doc2>>,
+    ) -> String {
+        self.xpath_steps(true, kept, renamed)
+    }
+
+    fn xpath_steps<'b, 'doc2: 'b>(
+        &self,
+        indexed: bool,
+        kept: &dyn Fn(XNode<'a, 'doc>) -> bool,
+        renamed: &dyn Fn(XNode<'a, 'doc>) -> Option<XNode<'b, 'doc2>>,
+    ) -> String {
+        let step = if self.is_attribute() {
+            format!("@{}", self.qname())
+        } else {
+            let xnode = |node| XNode { node, attr: None };
+            let siblings = self.node.parent().unwrap().children();
+            let (name, count, index) = if self.node.is_text() {
+                // adjacent texts are a single text node, such as the texts around a removed element
+                let mut runs = 0;
+                let mut index = None;
+                let mut previous_text = false;
+                for n in siblings.filter(|n| !(n.is_element() || n.is_text()) || kept(xnode(*n))) {
+                    if n.is_text() && !previous_text {
+                        runs += 1;
+                    }
+                    previous_text = n.is_text();
+                    if n == self.node {
+                        index = Some(runs);
+                    }
                 }
-                _ => unreachable!(),
+                ("text()".to_string(), runs, index.unwrap())
+            } else {
+                let name = |n: Node<'a, 'doc>| match renamed(xnode(n)) {
+                    Some(new) => {
+                        let tag_name = new.node.tag_name();
+                        let namespace = tag_name.namespace().map(str::to_string);
+                        (
+                            namespace,
+                            tag_name.name().to_string(),
+                            new.qname().to_string(),
+                        )
+                    }
+                    None => {
+                        let tag_name = n.tag_name();
+                        let namespace = tag_name.namespace().map(str::to_string);
+                        (
+                            namespace,
+                            tag_name.name().to_string(),
+                            xnode(n).qname().to_string(),
+                        )
+                    }
+                };
+                let (namespace, local_name, qname) = name(self.node);
+                let siblings: Vec<_> = siblings
+                    .filter(|n| {
+                        n.is_element() && kept(xnode(*n)) && {
+                            let (ns, local, _) = name(*n);
+                            ns == namespace && local == local_name
+                        }
+                    })
+                    .collect();
+                let index = siblings.iter().position(|n| *n == self.node).unwrap();
+                (qname, siblings.len(), index + 1)
+            };
+            if count > 1 || (indexed && self.parent().is_some()) {
+                format!("{}[{}]", name, index)
+            } else {
+                name
+            }
+        };
+        match self.parent() {
+            Some(parent) => format!("{}/{}", parent.xpath_steps(indexed, kept, renamed), step),
+            None => format!("/{}", step),
+        }
+    }
+
+    /// Check whether the text is merged with a previous text once the elements and texts for which
+    /// `kept` is false are removed.
+    pub(crate) fn follows_text(&self, kept: &dyn Fn(XNode<'a, 'doc>) -> bool) -> bool {
+        Self::merged_texts(self.node.prev_siblings(), kept)
+            .next()
+            .is_some()
+    }
+
+    /// Get the next texts merged with the text once the elements and texts for which `kept` is
+    /// false are removed.
+    pub(crate) fn following_texts(&self, kept: &dyn Fn(XNode<'a, 'doc>) -> bool) -> Vec<Self> {
+        Self::merged_texts(self.node.next_siblings(), kept).collect()
+    }
+
+    fn merged_texts(
+        siblings: impl Iterator<Item = Node<'a, 'doc>>,
+        kept: &dyn Fn(XNode<'a, 'doc>) -> bool,
+    ) -> impl Iterator<Item = Self> {
+        let xnode = |node| XNode { node, attr: None };
+        siblings
+            .skip(1)
+            .filter(move |n| !(n.is_element() || n.is_text()) || kept(xnode(*n)))
+            .take_while(|n| n.is_text())
+            .map(xnode)
+    }
+
+    pub(crate) fn hash(&self) -> Digest {
+        match self.attr {
+            Some(attr) => self.hash_with_value(attr.value()),
+            None if self.is_text() => {
+                self.hash_with_value(self.node.text().unwrap_or_default().trim())
+            }
+            None => {
+                let name = self.node.tag_name().name();
+                let namespace = self.node.tag_name().namespace().unwrap_or_default();
+                md5::compute(format!("{}:{}", namespace, name))
             }
         }
     }
 
-    pub(crate) fn signature(&self) -> Cow<str> {
+    /// Get the hash of an attribute or text as if it had another value.
+    pub(crate) fn hash_with_value(&self, value: &str) -> Digest {
+        match self.attr {
+            Some(attr) => md5::compute(format!(
+                "{}{}={}",
+                attr.namespace().unwrap_or_default(),
+                attr.name(),
+                value
+            )),
+            None => md5::compute(value),
+        }
+    }
+
+    pub(crate) fn signature(&self) -> Cow<'_, str> {
         if let Some(attr) = self.attr {
             Cow::Owned(format!(
                 "{}{}",
@@ -220,13 +526,24 @@ impl<'a, 'doc: 'a> XTree<'doc> {
     }
 
     /// Get the root node.
-    pub fn root(&self) -> XNode {
+    pub fn root(&self) -> XNode<'_, '_> {
         XNode {
             node: self.0.root_element(),
             attr: None,
         }
     }
 
+    /// Get the original text of the document.
+    pub fn text(&self) -> &'doc str {
+        self.0.input_text()
+    }
+
+    /// Get the nodes selected by an XPath expression, in document order. See [crate::xpath::XPath]
+    /// for the supported subset of XPath.
+    pub fn select(&self, xpath: &str) -> Result<Vec<XNode<'_, '_>>, XTreeError> {
+        Ok(XPath::parse(xpath)?.select(self))
+    }
+
     /// Get the underlying roxmltree::Document.
     pub fn get_roxmltree_doc(self) -> roxmltree::Document<'doc> {
         self.0
@@ -237,7 +554,7 @@ impl<'a, 'doc: 'a> XTree<'doc> {
 pub mod print {
     use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 
-    use crate::diff::{Edit, diff};
+    use crate::diff::{DiffOptions, Edit, diff_with_options};
 
     use super::{XNode, XTree};
     use std::{collections::HashMap, io::Write};
@@ -254,6 +571,7 @@ pub mod print {
         with_namespace: bool,
         indent: usize,
         color: bool,
+        diff_options: DiffOptions,
     }
 
     #[derive(Debug, Clone, Copy)]
@@ -262,6 +580,7 @@ pub mod print {
         Blank,
         Add,
         Delete,
+        Move,
     }
 
     impl GutterKind {
@@ -271,6 +590,7 @@ pub mod print {
                 GutterKind::Blank => " ",
                 GutterKind::Add => "+",
                 GutterKind::Delete => "-",
+                GutterKind::Move => "~",
             }
         }
     }
@@ -291,6 +611,7 @@ pub mod print {
                 indent: 3,
                 color: true,
                 with_namespace: false,
+                diff_options: DiffOptions::default(),
             }
         }
     }
@@ -310,6 +631,12 @@ pub mod print {
             self.with_namespace = yes;
             self
         }
+
+        /// Set the options used to calculate the difference.
+        pub fn with_diff_options(mut self, diff_options: DiffOptions) -> Self {
+            self.diff_options = diff_options;
+            self
+        }
     }
 
     impl PrintTreeOptions {
@@ -337,7 +664,7 @@ pub mod print {
         tree2: &XTree,
         options: PrintTreeDiffOptions,
     ) -> std::io::Result<()> {
-        let edits = diff(tree1, tree2);
+        let edits = diff_with_options(tree1, tree2, options.diff_options.clone());
 
         // trees are the same
         if edits.is_empty() {
@@ -345,80 +672,81 @@ pub mod print {
         }
 
         // trees are completely different
-        if matches!(edits[0], Edit::ReplaceRoot) {
-            let mut vlines = Vec::new();
-            write_subtree(
-                w,
-                tree1.root(),
-                &PrintTreeOptions::default()
-                    .with_indent(options.indent)
-                    .with_namespace(options.with_namespace),
-                GutterKind::Delete,
-                &mut vlines,
-            )?;
-            return write_subtree(
-                w,
-                tree2.root(),
-                &PrintTreeOptions::default()
-                    .with_indent(options.indent)
-                    .with_namespace(options.with_namespace),
-                GutterKind::Add,
-                &mut vlines,
-            );
+        if let [Edit::ReplaceRoot { old, new }] = edits[..] {
+            write_whole_subtree(w, old, &options, GutterKind::Delete)?;
+            return write_whole_subtree(w, new, &options, GutterKind::Add);
         }
 
         let mut changed_nodes = HashMap::new();
+        let mut renamed_nodes = HashMap::new();
         for e in edits {
+            // a renamed node is shown in place, its children are shown as usual
+            if let crate::diff::Edit::Rename { old, new }
+            | crate::diff::Edit::ReplaceRoot { old, new } = e
+            {
+                renamed_nodes.insert(old.id().to_string(), new);
+                continue;
+            }
+            // a moved node is shown both at its old and at its new place
+            if let crate::diff::Edit::Move { to_parent, .. } = e {
+                changed_nodes
+                    .entry(to_parent.id().to_string())
+                    .or_insert(Vec::new())
+                    .push(e.clone());
+            }
             let key = match e {
-                crate::diff::Edit::Insert {
-                    child_node: _,
-                    to_node,
-                } => to_node.id().to_string(),
+                crate::diff::Edit::Insert { to_node, .. } => to_node.id().to_string(),
                 crate::diff::Edit::Delete(node) => node.id().to_string(),
                 crate::diff::Edit::Update { old, new: _ } => old.id().to_string(),
-                crate::diff::Edit::ReplaceRoot => unreachable!(),
+                crate::diff::Edit::Reorder { old, .. } => old.id().to_string(),
+                crate::diff::Edit::Move { node, .. } => node.id().to_string(),
+                crate::diff::Edit::ReplaceRoot { .. } | crate::diff::Edit::Rename { .. } => {
+                    unreachable!()
+                }
             };
             changed_nodes.entry(key).or_insert(Vec::new()).push(e);
         }
 
         let mut vlines = Vec::new();
-        write_subtree_diff(w, tree1.root(), &changed_nodes, &options, &mut vlines)
+        write_subtree_diff(
+            w,
+            tree1.root(),
+            &changed_nodes,
+            &renamed_nodes,
+            &options,
+            &mut vlines,
+        )
     }
 
     fn write_subtree_diff<W: WriteColor>(
         w: &mut W,
         node: XNode,
         changed_nodes: &HashMap<String, Vec<Edit>>,
+        renamed_nodes: &HashMap<String, XNode>,
         options: &PrintTreeDiffOptions,
         vlines: &mut Vec<bool>,
     ) -> std::io::Result<()> {
         if let Some(edits) = changed_nodes.get(&node.id().to_string()) {
-            if matches!(edits[0], Edit::Insert { .. }) {
-                write_node_line(
-                    w,
-                    node,
-                    &PrintTreeOptions::default()
-                        .with_indent(options.indent)
-                        .with_namespace(options.with_namespace),
-                    GutterKind::Blank,
-                    vlines,
-                )?;
+            let is_parent = match edits[0] {
+                Edit::Insert { .. } => true,
+                Edit::Move { to_parent, .. } => to_parent == node,
+                _ => false,
+            };
+            if is_parent {
+                write_unchanged_node_line(w, node, renamed_nodes, options, vlines)?;
                 let children = node.children();
                 if children.is_empty() {
                     return Ok(());
                 }
                 vlines.push(true);
                 for child in children {
-                    write_subtree_diff(w, child, changed_nodes, options, vlines)?;
+                    write_subtree_diff(w, child, changed_nodes, renamed_nodes, options, vlines)?;
                 }
             }
             let last_index = edits.len() - 1;
             for (i, e) in edits.iter().enumerate() {
                 match e {
-                    Edit::Insert {
-                        child_node,
-                        to_node: _,
-                    } => {
+                    Edit::Insert { child_node, .. } => {
                         if i == last_index {
                             *vlines.last_mut().unwrap() = false;
                         }
@@ -461,22 +789,53 @@ pub mod print {
                             vlines,
                         )?;
                     }
-                    Edit::ReplaceRoot => unreachable!(),
+                    Edit::Reorder { .. } => write_subtree(
+                        w,
+                        node,
+                        &PrintTreeOptions::default()
+                            .with_indent(options.indent)
+                            .with_namespace(options.with_namespace),
+                        GutterKind::Move,
+                        vlines,
+                    )?,
+                    Edit::Move {
+                        node: moved,
+                        to_parent,
+                        ..
+                    } => {
+                        if *to_parent == node {
+                            if i == last_index {
+                                *vlines.last_mut().unwrap() = false;
+                            }
+                            write_subtree(
+                                w,
+                                *moved,
+                                &PrintTreeOptions::default()
+                                    .with_indent(options.indent)
+                                    .with_namespace(options.with_namespace),
+                                GutterKind::Move,
+                                vlines,
+                            )?;
+                        } else {
+                            write_subtree(
+                                w,
+                                node,
+                                &PrintTreeOptions::default()
+                                    .with_indent(options.indent)
+                                    .with_namespace(options.with_namespace),
+                                GutterKind::Delete,
+                                vlines,
+                            )?;
+                        }
+                    }
+                    Edit::ReplaceRoot { .. } | Edit::Rename { .. } => unreachable!(),
                 }
             }
-            if matches!(edits[0], Edit::Insert { .. }) {
+            if is_parent {
                 vlines.pop();
             }
         } else {
-            write_node_line(
-                w,
-                node,
-                &PrintTreeOptions::default()
-                    .with_indent(options.indent)
-                    .with_namespace(options.with_namespace),
-                GutterKind::Blank,
-                vlines,
-            )?;
+            write_unchanged_node_line(w, node, renamed_nodes, options, vlines)?;
             let children = node.children();
             if children.is_empty() {
                 return Ok(());
@@ -487,13 +846,67 @@ pub mod print {
                 if i == last_index {
                     *vlines.last_mut().unwrap() = false;
                 }
-                write_subtree_diff(w, child, changed_nodes, options, vlines)?;
+                write_subtree_diff(w, child, changed_nodes, renamed_nodes, options, vlines)?;
             }
             vlines.pop();
         }
         Ok(())
     }
 
+    /// Write the line of a node whose subtree is shown, taking a rename of the node into account.
+    fn write_unchanged_node_line<W: WriteColor>(
+        w: &mut W,
+        node: XNode,
+        renamed_nodes: &HashMap<String, XNode>,
+        options: &PrintTreeDiffOptions,
+        vlines: &mut [bool],
+    ) -> std::io::Result<()> {
+        let print_options = PrintTreeOptions::default()
+            .with_indent(options.indent)
+            .with_namespace(options.with_namespace);
+        if let Some(new) = renamed_nodes.get(&node.id().to_string()) {
+            write_node_line(w, node, &print_options, GutterKind::Delete, vlines)?;
+            write_node_line(w, *new, &print_options, GutterKind::Add, vlines)
+        } else {
+            write_node_line(w, node, &print_options, GutterKind::Blank, vlines)
+        }
+    }
+
+    /// Write the whole tree as inserted, e.g. for a file compared to no file.
+    pub fn write_inserted_tree<W: WriteColor>(
+        w: &mut W,
+        tree: &XTree,
+        options: PrintTreeDiffOptions,
+    ) -> std::io::Result<()> {
+        write_whole_subtree(w, tree.root(), &options, GutterKind::Add)
+    }
+
+    /// Write the whole tree as deleted, e.g. for no file compared to a file.
+    pub fn write_deleted_tree<W: WriteColor>(
+        w: &mut W,
+        tree: &XTree,
+        options: PrintTreeDiffOptions,
+    ) -> std::io::Result<()> {
+        write_whole_subtree(w, tree.root(), &options, GutterKind::Delete)
+    }
+
+    fn write_whole_subtree<W: WriteColor>(
+        w: &mut W,
+        node: XNode,
+        options: &PrintTreeDiffOptions,
+        gutter: GutterKind,
+    ) -> std::io::Result<()> {
+        write_subtree(
+            w,
+            node,
+            &PrintTreeOptions::default()
+                .with_indent(options.indent)
+                .with_namespace(options.with_namespace),
+            gutter,
+            &mut Vec::new(),
+        )
+    }
+
     /// Print the tree to stdout
     pub fn print_tree(tree: &XTree, options: PrintTreeOptions) {
         let mut stdout = StandardStream::stdout(ColorChoice::Never);
@@ -582,6 +995,7 @@ pub mod print {
             GutterKind::Blank => w.reset(),
             GutterKind::Add => w.set_color(ColorSpec::new().set_fg(Some(Color::Green))),
             GutterKind::Delete => w.set_color(ColorSpec::new().set_fg(Some(Color::Red))),
+            GutterKind::Move => w.set_color(ColorSpec::new().set_fg(Some(Color::Yellow))),
         }
     }
 
@@ -726,3 +1140,36 @@ pub mod print {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_xpath() {
+        let text = std::fs::read_to_string("test/file1.xml").unwrap();
+        let tree = XTree::parse(&text).unwrap();
+        let customer = tree.root().children()[0];
+        let address = customer.children()[4];
+        let street = address.children()[0];
+        assert_eq!(customer.xpath(), "/Profile/Customer");
+        assert_eq!(address.xpath(), "/Profile/Customer/Address[2]");
+        assert_eq!(
+            street.children()[1].xpath(),
+            "/Profile/Customer/Address[2]/StreetNmbr/@POBox"
+        );
+        assert_eq!(
+            street.children()[0].xpath(),
+            "/Profile/Customer/Address[2]/StreetNmbr/text()"
+        );
+
+        let tree = XTree::parse(r#"<n:a xmlns:n="urn:n">1<b/>2<n:b n:x="3"/></n:a>"#).unwrap();
+        let paths: Vec<_> = tree.root().children().iter().map(XNode::xpath).collect();
+        assert_eq!(
+            paths,
+            ["/n:a/text()[1]", "/n:a/b", "/n:a/text()[2]", "/n:a/n:b"]
+        );
+        let attribute = tree.root().children()[3].children()[0];
+        assert_eq!(attribute.xpath(), "/n:a/n:b/@n:x");
+    }
+}
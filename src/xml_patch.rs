@@ -0,0 +1,612 @@
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+};
+
+use crate::{
+    diff::{Diff, Edit},
+    tree::XNode,
+};
+
+/// Write the edits of a diff as an XML Patch document of RFC 5261, to be applied to the first
+/// document.
+///
+/// The `<diff>` element holds `<replace>` operations for the updated values and the renamed or
+/// replaced elements, `<remove>` operations for the deleted nodes and `<add>` operations for the
+/// inserted nodes, with moved and reordered nodes removed and then added. The `sel` selectors are
+/// absolute XPaths with a position predicate, such as `/Profile/Customer[1]/Address[2]/@Type`,
+/// which take the previous operations into account. The namespaces are declared with the prefixes
+/// of the documents, on the `<diff>` element if all the operations bind a prefix to the same
+/// namespace, and otherwise on each operation, or on the added elements which bind it to another
+/// namespace. An added attribute whose prefix is bound to another namespace gets a new prefix.
+pub fn to_xml_patch(diff: &Diff) -> String {
+    let mut patch = XmlPatch::default();
+    for edit in diff.iter() {
+        patch.add(edit);
+    }
+    patch.finish()
+}
+
+/// Prefixes and URIs of namespaces.
+type Namespaces = Vec<(Option<String>, String)>;
+
+/// Markup to add or replace with, with the namespaces it uses from the scope of its node.
+#[derive(Clone)]
+struct Content {
+    markup: String,
+    namespaces: Namespaces,
+}
+
+impl Content {
+    /// The escaped value of an attribute or text.
+    fn value(value: &str) -> Self {
+        Self {
+            markup: escape(value),
+            namespaces: Vec::new(),
+        }
+    }
+
+    /// An element or text, with the namespaces that an element inherits from its ancestors.
+    fn node(node: XNode) -> Self {
+        if node.is_text() {
+            return Self::value(node.value().unwrap());
+        }
+        let markup = node.source().to_string();
+        let tag = &markup[..node.start_tag_end() - node.range().start];
+        let inherited = match node.parent() {
+            Some(parent) => scope(parent),
+            None => vec![(None, String::new())],
+        };
+        let namespaces = inherited
+            .into_iter()
+            .filter(|(prefix, _)| !declares(tag, prefix.as_deref()))
+            .collect();
+        Self { markup, namespaces }
+    }
+}
+
+/// Node to add to a parent.
+enum Addition<'a, 'doc> {
+    Attribute(XNode<'a, 'doc>),
+    /// An element or text, at a position among the children of the parent.
+    Child(usize, Content),
+}
+
+/// An operation of the patch, with the namespaces used by its selector and content.
+struct Operation {
+    name: &'static str,
+    /// The attributes of the start tag, such as `sel="/a/b[1]"`.
+    attributes: String,
+    content: Option<String>,
+    namespaces: Namespaces,
+}
+
+impl Operation {
+    fn new(name: &'static str, attributes: String, namespaces: Namespaces) -> Self {
+        Self {
+            name,
+            attributes,
+            content: None,
+            namespaces,
+        }
+    }
+
+    /// Add content, declaring the namespaces it uses on its element if they conflict with those of
+    /// the operation.
+    fn push(&mut self, content: &Content) {
+        let conflicts = declare(&mut self.namespaces, &content.namespaces);
+        let mut markup = content.markup.clone();
+        if !conflicts.is_empty() {
+            let qname_end = markup[1..]
+                .find(|c: char| c.is_whitespace() || c == '/' || c == '>')
+                .unwrap()
+                + 1;
+            markup.insert_str(qname_end, &namespace_attributes(&conflicts));
+        }
+        self.content.get_or_insert_default().push_str(&markup);
+    }
+
+    fn write(&self, declared: &Namespaces) -> String {
+        let namespaces: Namespaces = self
+            .namespaces
+            .iter()
+            .filter(|ns| !declared.contains(ns))
+            .cloned()
+            .collect();
+        let start = format!(
+            "<{} {}{}",
+            self.name,
+            self.attributes,
+            namespace_attributes(&namespaces)
+        );
+        match &self.content {
+            Some(content) => format!("{start}>{content}</{}>", self.name),
+            None => format!("{start}/>"),
+        }
+    }
+}
+
+/// Collect the operations of the edits, and write them in an order which keeps the selectors valid.
+#[derive(Default)]
+struct XmlPatch<'a, 'doc1, 'doc2> {
+    /// Nodes whose value or subtree is replaced, with the new value or subtree.
+    replaced: Vec<(XNode<'a, 'doc1>, Content)>,
+    /// Renamed elements, by id, with the element of the second document.
+    renamed: HashMap<String, XNode<'a, 'doc2>>,
+    removed: Vec<XNode<'a, 'doc1>>,
+    /// Nodes added to each parent.
+    added: Vec<(XNode<'a, 'doc1>, Addition<'a, 'doc2>)>,
+    /// Moved or reordered nodes, which are also in `removed`.
+    moved: Vec<(XNode<'a, 'doc1>, usize, XNode<'a, 'doc1>)>,
+}
+
+impl<'a, 'doc1, 'doc2> XmlPatch<'a, 'doc1, 'doc2> {
+    fn add(&mut self, edit: &Edit<'a, 'doc1, 'doc2>) {
+        match *edit {
+            Edit::Insert {
+                child_node,
+                to_node,
+                position,
+            } => {
+                let addition = if child_node.is_attribute() {
+                    Addition::Attribute(child_node)
+                } else {
+                    Addition::Child(position, Content::node(child_node))
+                };
+                self.added.push((to_node, addition));
+            }
+            Edit::Delete(node) => self.removed.push(node),
+            Edit::Update { old, new } => {
+                self.replaced
+                    .push((old, Content::value(new.value().unwrap())));
+            }
+            Edit::ReplaceRoot { old, new } | Edit::Rename { old, new } => {
+                if matches!(edit, Edit::Rename { .. }) {
+                    self.renamed.insert(old.id().to_string(), new);
+                }
+                self.replaced.push((old, Content::node(new)));
+            }
+            Edit::Reorder { old, to, .. } => {
+                self.removed.push(old);
+                self.moved.push((old.parent().unwrap(), to, old));
+            }
+            Edit::Move {
+                node,
+                to_parent,
+                position,
+                ..
+            } => {
+                self.removed.push(node);
+                self.moved.push((to_parent, position, node));
+            }
+        }
+    }
+    /// Write the operations: the removals of the texts and then of the other nodes, each from the
+    /// last node to the first one, so that neither the positions in the selectors of the next ones
+    /// change nor texts merge before they are removed, then the replacements and the additions in
+    /// the same order. Children are added before a remaining sibling, or appended to their parent.
+    fn finish(mut self) -> String {
+        // the subtree of a renamed or replaced element is replaced as a whole
+        let replaced_elements: Vec<_> = self
+            .replaced
+            .iter()
+            .map(|(node, _)| *node)
+            .filter(|node| node.is_element())
+            .collect();
+        let covered = |node: &XNode| replaced_elements.iter().any(|e| node.is_within(*e));
+        self.removed.retain(|node| !covered(node));
+        self.added.retain(|(parent, _)| !covered(parent));
+        self.moved.retain(|(parent, _, _)| !covered(parent));
+        self.replaced.retain(|(node, _)| {
+            !replaced_elements
+                .iter()
+                .any(|e| node != e && node.is_within(*e))
+        });
+
+        // a text merged with the previous one once the nodes between them are removed cannot be
+        // located anymore, so it is removed too and added back after the nodes added before it
+        let mut readded: HashMap<String, (XNode, Content)> = HashMap::new();
+        let mut removed_texts = Vec::new();
+        {
+            let removed: HashSet<_> = self.removed.iter().map(|n| n.id().to_string()).collect();
+            let kept = |node: XNode<'a, 'doc1>| !removed.contains(&node.id().to_string());
+            let parents: HashMap<_, _> = self
+                .removed
+                .iter()
+                .filter(|node| !node.is_attribute())
+                .filter_map(XNode::parent)
+                .map(|parent| (parent.id().to_string(), parent))
+                .collect();
+            for parent in parents.into_values() {
+                for child in parent.children() {
+                    if child.is_text() && kept(child) && child.follows_text(&kept) {
+                        readded.insert(child.id().to_string(), (parent, Content::node(child)));
+                        removed_texts.push(child);
+                    }
+                }
+            }
+        }
+        self.replaced.retain(
+            |(node, text)| match readded.get_mut(&node.id().to_string()) {
+                Some((_, content)) => {
+                    content.clone_from(text);
+                    false
+                }
+                None => true,
+            },
+        );
+        self.removed.extend(removed_texts);
+
+        let mut operations = Vec::new();
+        let no_rename = |_| None::<XNode>;
+        self.removed
+            .sort_by_key(|node| (!node.is_text(), Reverse(node.range().start)));
+        for node in &self.removed {
+            let (sel, namespaces) = selector(*node, &|_| true, &no_rename);
+            operations.push(Operation::new("remove", sel, namespaces));
+        }
+
+        let removed: HashSet<_> = self.removed.iter().map(|n| n.id().to_string()).collect();
+        let kept = |node: XNode<'a, 'doc1>| !removed.contains(&node.id().to_string());
+        self.replaced
+            .sort_by_key(|(node, _)| Reverse(node.range().start));
+        for (node, content) in &self.replaced {
+            let (sel, namespaces) = selector(*node, &kept, &no_rename);
+            let mut operation = Operation::new("replace", sel, namespaces);
+            operation.push(content);
+            // with the texts merged with it, which are whitespace since the others are added back
+            if node.is_text() {
+                for text in node.following_texts(&kept) {
+                    operation.push(&Content::node(text));
+                }
+            }
+            operations.push(operation);
+        }
+
+        let renamed = |node: XNode<'a, 'doc1>| self.renamed.get(&node.id().to_string()).copied();
+        let mut children: HashMap<String, (XNode, Vec<(usize, Content)>)> = HashMap::new();
+        for (parent, addition) in self.added {
+            match addition {
+                Addition::Attribute(attribute) => {
+                    let (sel, mut namespaces) = selector(parent, &kept, &renamed);
+                    let qname = attribute_qname(attribute, &mut namespaces);
+                    let attributes = format!(r#"{sel} type="@{}""#, escape(&qname));
+                    let mut operation = Operation::new("add", attributes, namespaces);
+                    operation.push(&Content::value(attribute.value().unwrap()));
+                    operations.push(operation);
+                }
+                Addition::Child(position, content) => children
+                    .entry(parent.id().to_string())
+                    .or_insert_with(|| (parent, Vec::new()))
+                    .1
+                    .push((position, content)),
+            }
+        }
+        for (parent, position, node) in self.moved {
+            children
+                .entry(parent.id().to_string())
+                .or_insert_with(|| (parent, Vec::new()))
+                .1
+                .push((position, Content::node(node)));
+        }
+        for (parent, _) in readded.values() {
+            children
+                .entry(parent.id().to_string())
+                .or_insert_with(|| (*parent, Vec::new()));
+        }
+
+        // the offset in the first document where the children are added, and the operation
+        let mut additions = Vec::new();
+        for (parent, mut added) in children.into_values() {
+            added.sort_by_key(|(position, _)| *position);
+            // the children in the end, with the content of the added ones
+            let child = |child: XNode<'a, 'doc1>| match readded.get(&child.id().to_string()) {
+                Some((_, content)) => (None, Some(content.clone())),
+                None => (Some(child), None),
+            };
+            let mut remaining = parent
+                .children()
+                .into_iter()
+                .filter(|child| {
+                    !child.is_attribute()
+                        && (kept(*child) || readded.contains_key(&child.id().to_string()))
+                })
+                .map(child);
+            let mut result = Vec::new();
+            for (position, content) in added {
+                while result.len() < position
+                    && let Some(child) = remaining.next()
+                {
+                    result.push(child);
+                }
+                result.push((None, Some(content)));
+            }
+            result.extend(remaining);
+
+            let operation = |node: XNode<'a, 'doc1>, pos: &str, contents: &[Content]| {
+                let (sel, namespaces) = selector(node, &kept, &renamed);
+                let mut operation = Operation::new("add", sel + pos, namespaces);
+                for content in contents {
+                    operation.push(content);
+                }
+                operation
+            };
+            let mut contents = Vec::new();
+            for (anchor, content) in result {
+                match (anchor, content) {
+                    (Some(anchor), _) if !contents.is_empty() => {
+                        let operation = operation(anchor, r#" pos="before""#, &contents);
+                        additions.push((anchor.range().start, operation));
+                        contents.clear();
+                    }
+                    (_, Some(content)) => contents.push(content),
+                    _ => {}
+                }
+            }
+            if !contents.is_empty() {
+                additions.push((parent.range().end, operation(parent, "", &contents)));
+            }
+        }
+        // from the last one to the first one, so that the positions in the selectors of the next
+        // ones do not change
+        additions.sort_by_key(|(offset, _)| Reverse(*offset));
+        operations.extend(additions.into_iter().map(|(_, operation)| operation));
+
+        // the prefixes bound to a single namespace by all the operations are declared once
+        let mut declared: Namespaces = Vec::new();
+        let mut conflicting: Namespaces = Vec::new();
+        for operation in &operations {
+            conflicting.extend(declare(&mut declared, &operation.namespaces));
+        }
+        declared.retain(|(prefix, _)| !conflicting.iter().any(|(p, _)| p == prefix));
+        let mut patch = String::from("<diff");
+        patch += &namespace_attributes(
+            &declared
+                .iter()
+                .filter(|ns| **ns != (None, String::new()))
+                .cloned()
+                .collect::<Vec<_>>(),
+        );
+        if !declared.iter().any(|(prefix, _)| prefix.is_none()) {
+            declared.push((None, String::new()));
+        }
+        if operations.is_empty() {
+            return patch + "/>";
+        }
+        patch.push_str(">\n");
+        for operation in operations {
+            patch += &format!("  {}\n", operation.write(&declared));
+        }
+        patch + "</diff>"
+    }
+}
+
+/// Get the `sel` attribute of an operation on a node, with the namespaces used by the selector.
+fn selector<'a, 'doc1, 'b, 'doc2: 'b>(
+    node: XNode<'a, 'doc1>,
+    kept: &dyn Fn(XNode<'a, 'doc1>) -> bool,
+    renamed: &dyn Fn(XNode<'a, 'doc1>) -> Option<XNode<'b, 'doc2>>,
+) -> (String, Namespaces) {
+    let sel = format!(r#"sel="{}""#, escape(&node.xpath_with(kept, renamed)));
+    let mut namespaces = scope(node);
+    let mut ancestor = Some(node);
+    while let Some(node) = ancestor {
+        if let Some(new) = renamed(node) {
+            declare(&mut namespaces, &scope(new));
+        }
+        ancestor = node.parent();
+    }
+    (sel, namespaces)
+}
+
+/// Get the qualified name of an added attribute, with a new prefix if its prefix is bound to
+/// another namespace by the operation.
+fn attribute_qname(attribute: XNode, namespaces: &mut Namespaces) -> String {
+    let qname = attribute.qname();
+    let Some((prefix, name)) = qname.split_once(':') else {
+        return qname.to_string();
+    };
+    let uri = attribute
+        .lookup_namespace_uri(Some(prefix))
+        .unwrap_or_default();
+    let binding = |prefix: &str, namespaces: &Namespaces| {
+        namespaces
+            .iter()
+            .find(|(p, _)| p.as_deref() == Some(prefix))
+            .map(|(_, uri)| uri.clone())
+    };
+    let prefix = match binding(prefix, namespaces) {
+        Some(bound) if bound != uri => (1..)
+            .map(|i| format!("{prefix}{i}"))
+            .find(|p| binding(p, namespaces).is_none())
+            .unwrap(),
+        _ => prefix.to_string(),
+    };
+    declare(namespaces, &vec![(Some(prefix.clone()), uri.to_string())]);
+    format!("{prefix}:{name}")
+}
+
+/// Get the namespaces in scope at a node, with an empty default namespace if none is declared.
+fn scope(node: XNode) -> Namespaces {
+    let mut namespaces: Namespaces = node
+        .namespaces()
+        .into_iter()
+        .filter(|(prefix, _)| *prefix != Some("xml"))
+        .map(|(prefix, uri)| (prefix.map(str::to_string), uri.to_string()))
+        .collect();
+    if !namespaces.iter().any(|(prefix, _)| prefix.is_none()) {
+        namespaces.push((None, String::new()));
+    }
+    namespaces
+}
+
+/// Add namespaces to the declared ones, and return those whose prefix is declared with another
+/// namespace.
+fn declare(declared: &mut Namespaces, namespaces: &Namespaces) -> Namespaces {
+    let mut conflicts = Vec::new();
+    for (prefix, uri) in namespaces {
+        match declared.iter().find(|(p, _)| p == prefix) {
+            Some((_, u)) if u != uri => conflicts.push((prefix.clone(), uri.clone())),
+            Some(_) => {}
+            None => declared.push((prefix.clone(), uri.clone())),
+        }
+    }
+    conflicts
+}
+
+/// Check whether a start tag declares a namespace prefix, or the default namespace.
+fn declares(tag: &str, prefix: Option<&str>) -> bool {
+    let name = match prefix {
+        Some(prefix) => format!("xmlns:{prefix}"),
+        None => "xmlns".to_string(),
+    };
+    tag.match_indices(&name).any(|(i, _)| {
+        tag[..i].ends_with(char::is_whitespace)
+            && tag[i + name.len()..].trim_start().starts_with('=')
+    })
+}
+
+fn namespace_attributes(namespaces: &Namespaces) -> String {
+    let mut attributes = String::new();
+    for (prefix, uri) in namespaces {
+        match prefix {
+            Some(prefix) => attributes += &format!(r#" xmlns:{}="{}""#, prefix, escape(uri)),
+            None => attributes += &format!(r#" xmlns="{}""#, escape(uri)),
+        }
+    }
+    attributes
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        diff::{ChildOrder, DiffOptions, diff, diff_with_options},
+        tree::XTree,
+    };
+
+    #[test]
+    fn test_to_xml_patch() {
+        let tree1 = XTree::parse(r#"<a><b x="1">one</b><b x="2">two</b><c/></a>"#).unwrap();
+        let tree2 = XTree::parse(r#"<a><b x="2" y="&lt;">2</b><c><d/></c></a>"#).unwrap();
+        assert_eq!(
+            to_xml_patch(&diff(&tree1, &tree2)),
+            r#"<diff>
+  <remove sel="/a/b[1]"/>
+  <replace sel="/a/b[1]/text()[1]">2</replace>
+  <add sel="/a/b[1]" type="@y">&lt;</add>
+  <add sel="/a/c[1]"><d/></add>
+</diff>"#
+        );
+        assert_eq!(to_xml_patch(&diff(&tree1, &tree1)), "<diff/>");
+    }
+
+    #[test]
+    fn test_to_xml_patch_rename() {
+        let tree1 = XTree::parse(r#"<n:a xmlns:n="urn:n"><n:b><c/></n:b><n:b/></n:a>"#).unwrap();
+        let tree2 =
+            XTree::parse(r#"<n:a xmlns:n="urn:n"><n:e><c/><d/></n:e><n:b>x</n:b></n:a>"#).unwrap();
+        let options = DiffOptions::default().with_rename_detection(0.5);
+        assert_eq!(
+            to_xml_patch(&diff_with_options(&tree1, &tree2, options)),
+            r#"<diff xmlns:n="urn:n">
+  <replace sel="/n:a/n:b[1]"><n:e><c/><d/></n:e></replace>
+  <add sel="/n:a/n:b[1]">x</add>
+</diff>"#
+        );
+    }
+
+    #[test]
+    fn test_to_xml_patch_ordered() {
+        let tree1 = XTree::parse("<a><b/><c/><b>1</b><d/></a>").unwrap();
+        let tree2 = XTree::parse("<a><c/><b/><e/><b>1</b><b/></a>").unwrap();
+        let options = DiffOptions::default().with_child_order(ChildOrder::Ordered);
+        assert_eq!(
+            to_xml_patch(&diff_with_options(&tree1, &tree2, options)),
+            r#"<diff>
+  <remove sel="/a/d[1]"/>
+  <remove sel="/a/b[1]"/>
+  <add sel="/a"><b/></add>
+  <add sel="/a/b[1]" pos="before"><b/><e/></add>
+</diff>"#
+        );
+    }
+
+    #[test]
+    fn test_to_xml_patch_merged_texts() {
+        // the texts around a removed element merge: the first one is removed before
+        let tree1 = XTree::parse("<a>x<b/>y</a>").unwrap();
+        let tree2 = XTree::parse("<a>y</a>").unwrap();
+        assert_eq!(
+            to_xml_patch(&diff(&tree1, &tree2)),
+            r#"<diff>
+  <remove sel="/a/text()[1]"/>
+  <remove sel="/a/b[1]"/>
+</diff>"#
+        );
+        // the indentation around a removed element merges, which shifts the next texts
+        let tree1 = XTree::parse("<a>\n <b/>\n <c/>t</a>").unwrap();
+        let tree2 = XTree::parse("<a>\n <c/>u</a>").unwrap();
+        assert_eq!(
+            to_xml_patch(&diff(&tree1, &tree2)),
+            r#"<diff>
+  <remove sel="/a/b[1]"/>
+  <replace sel="/a/text()[2]">u</replace>
+</diff>"#
+        );
+        // the second text is added back after the element added between them
+        let tree1 = XTree::parse("<a>x<b/>y</a>").unwrap();
+        let tree2 = XTree::parse("<a>x<c/>y</a>").unwrap();
+        assert_eq!(
+            to_xml_patch(&diff(&tree1, &tree2)),
+            r#"<diff>
+  <remove sel="/a/text()[2]"/>
+  <remove sel="/a/b[1]"/>
+  <add sel="/a"><c/>y</add>
+</diff>"#
+        );
+    }
+
+    #[test]
+    fn test_to_xml_patch_namespaces() {
+        // the prefix `p` is bound to another namespace in the second document
+        let tree1 = XTree::parse(r#"<a xmlns:p="urn:1"><p:b/><c/><d/></a>"#).unwrap();
+        let tree2 = XTree::parse(
+            r#"<a xmlns:p="urn:2"><p:b xmlns:p="urn:1"/><c p:x="1"/><d><p:e/></d></a>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            to_xml_patch(&diff(&tree1, &tree2)),
+            r#"<diff xmlns:p="urn:1" xmlns:p1="urn:2">
+  <add sel="/a/c[1]" type="@p1:x">1</add>
+  <add sel="/a/d[1]"><p:e xmlns:p="urn:2"/></add>
+</diff>"#
+        );
+        // the prefix `p` is bound to two namespaces by the selectors
+        let tree1 =
+            XTree::parse(r#"<a><b xmlns:p="urn:1"><p:c/></b><d xmlns:p="urn:2"><p:c/></d></a>"#)
+                .unwrap();
+        let tree2 = XTree::parse("<a><b/><d/></a>").unwrap();
+        assert_eq!(
+            to_xml_patch(&diff(&tree1, &tree2)),
+            r#"<diff>
+  <remove sel="/a/d[1]/p:c[1]" xmlns:p="urn:2"/>
+  <remove sel="/a/b[1]/p:c[1]" xmlns:p="urn:1"/>
+</diff>"#
+        );
+    }
+}
@@ -0,0 +1,439 @@
+use crate::tree::{XNode, XNodeName, XTree, XTreeError};
+
+/// A parsed XPath expression, in the subset of XPath 1.0 used to locate nodes:
+///
+/// - absolute and relative location paths, with the child `/` and descendant `//` axes;
+/// - element steps `name`, `prefix:name`, `prefix:*` and `*`, attribute steps `@name`, `@*`, and
+///   text steps `text()`;
+/// - predicates `[2]` on the position among the nodes selected under the same parent,
+///   `[@name]` on the presence of an attribute and `[@name='value']` on its value.
+///
+/// A prefix stands for the namespace it is bound to in the document, at the selected node, so the
+/// XPaths of [XNode::xpath] select their node. An unprefixed element name is in the default
+/// namespace of the document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XPath {
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Step {
+    /// Whether the step selects descendants with `//`, rather than children.
+    descendant: bool,
+    test: NodeTest,
+    predicates: Vec<Predicate>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum NodeTest {
+    Element(NameTest),
+    Attribute(NameTest),
+    Text,
+}
+
+/// A name with an optional prefix, where `None` local name stands for `*`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct NameTest {
+    prefix: Option<String>,
+    local: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Predicate {
+    /// 1-based position.
+    Position(usize),
+    Attribute {
+        name: NameTest,
+        value: Option<String>,
+    },
+}
+
+impl XPath {
+    /// Parse an XPath expression.
+    pub fn parse(xpath: &str) -> Result<Self, XTreeError> {
+        let error = |reason: &str| Err(XTreeError::InvalidXPath(format!("{reason} in {xpath:?}")));
+        let mut rest = xpath.trim();
+        if rest.is_empty() {
+            return error("empty expression");
+        }
+        let mut steps = Vec::new();
+        while !rest.is_empty() {
+            let descendant = if let Some(r) = rest.strip_prefix("//") {
+                rest = r;
+                true
+            } else if let Some(r) = rest.strip_prefix('/') {
+                rest = r;
+                false
+            } else if steps.is_empty() {
+                // relative to the document
+                false
+            } else {
+                return error("expected /");
+            };
+
+            let test = if let Some(r) = rest.strip_prefix('@') {
+                rest = r;
+                NodeTest::Attribute(parse_name(&mut rest).ok_or_else(|| invalid(xpath))?)
+            } else if let Some(r) = rest.strip_prefix("text()") {
+                rest = r;
+                NodeTest::Text
+            } else {
+                NodeTest::Element(parse_name(&mut rest).ok_or_else(|| invalid(xpath))?)
+            };
+
+            let mut predicates = Vec::new();
+            while let Some(r) = rest.strip_prefix('[') {
+                let Some(end) = predicate_end(r) else {
+                    return error("unclosed predicate");
+                };
+                predicates.push(parse_predicate(&r[..end]).ok_or_else(|| invalid(xpath))?);
+                rest = &r[end + 1..];
+            }
+            steps.push(Step {
+                descendant,
+                test,
+                predicates,
+            });
+        }
+        Ok(Self { steps })
+    }
+
+    /// Get the nodes of a tree selected by the expression, in document order.
+    pub fn select<'a, 'doc>(&self, tree: &'a XTree<'doc>) -> Vec<XNode<'a, 'a>> {
+        self.select_from(tree.root())
+    }
+
+    /// Check whether the expression selects the node in its tree.
+    pub fn matches(&self, node: XNode) -> bool {
+        self.matches_steps(self.steps.len() - 1, node)
+    }
+
+    /// Check whether the steps up to `index` select the node, from the node up to the document, so
+    /// that only its ancestors and their children are visited.
+    fn matches_steps(&self, index: usize, node: XNode) -> bool {
+        let step = &self.steps[index];
+        // `None` is the document, whose only child is the root element
+        let parent = node.parent();
+        if !step.select(parent, node).contains(&node) {
+            return false;
+        }
+        let Some(parent) = parent else {
+            return index == 0;
+        };
+        if index == 0 {
+            return step.descendant;
+        }
+        if !step.descendant {
+            return self.matches_steps(index - 1, parent);
+        }
+        let mut ancestor = Some(parent);
+        while let Some(a) = ancestor {
+            if self.matches_steps(index - 1, a) {
+                return true;
+            }
+            ancestor = a.parent();
+        }
+        false
+    }
+
+    /// Get the nodes selected in the tree of the root element.
+    pub(crate) fn select_from<'a, 'doc>(&self, root: XNode<'a, 'doc>) -> Vec<XNode<'a, 'doc>> {
+        // `None` is the document, whose only child is the root element
+        let mut context = vec![None];
+        for step in &self.steps {
+            if step.descendant {
+                context = context
+                    .into_iter()
+                    .flat_map(|node| {
+                        let mut nodes = vec![node];
+                        let mut stack: Vec<_> = children(node, root);
+                        while let Some(n) = stack.pop() {
+                            if n.is_element() {
+                                nodes.push(Some(n));
+                                stack.extend(n.child_nodes());
+                            }
+                        }
+                        nodes
+                    })
+                    .collect();
+            }
+            let mut selected: Vec<_> = context
+                .into_iter()
+                .flat_map(|node| step.select(node, root))
+                .collect();
+            selected.sort_by_key(|n| n.range().start);
+            selected.dedup();
+            context = selected.into_iter().map(Some).collect();
+        }
+        context.into_iter().flatten().collect()
+    }
+}
+
+impl Step {
+    /// Get the nodes selected by the step under a node.
+    fn select<'a, 'doc>(
+        &self,
+        node: Option<XNode<'a, 'doc>>,
+        root: XNode<'a, 'doc>,
+    ) -> Vec<XNode<'a, 'doc>> {
+        let mut nodes: Vec<_> = match &self.test {
+            NodeTest::Element(name) => children(node, root)
+                .into_iter()
+                .filter(|n| n.is_element() && name.matches_element(*n))
+                .collect(),
+            NodeTest::Attribute(name) => node.map_or_else(Vec::new, |node| {
+                node.children()
+                    .into_iter()
+                    .filter(|n| n.is_attribute() && name.matches_attribute(*n))
+                    .collect()
+            }),
+            NodeTest::Text => children(node, root)
+                .into_iter()
+                .filter(|n| n.is_text())
+                .collect(),
+        };
+        for predicate in &self.predicates {
+            nodes = match predicate {
+                Predicate::Position(position) => {
+                    nodes.get(position - 1).copied().into_iter().collect()
+                }
+                Predicate::Attribute { name, value } => nodes
+                    .into_iter()
+                    .filter(|n| {
+                        n.children().iter().any(|a| {
+                            a.is_attribute()
+                                && name.matches_attribute(*a)
+                                && value.as_deref().is_none_or(|v| a.value() == Some(v))
+                        })
+                    })
+                    .collect(),
+            };
+        }
+        nodes
+    }
+}
+
+impl NameTest {
+    fn matches_element(&self, node: XNode) -> bool {
+        let XNodeName::TagName(tag_name) = node.name() else {
+            return false;
+        };
+        if self.local.as_deref().is_some_and(|l| l != tag_name.name()) {
+            return false;
+        }
+        match &self.prefix {
+            Some(prefix) => {
+                let namespace = node.lookup_namespace_uri(Some(prefix));
+                namespace.is_some() && namespace == tag_name.namespace()
+            }
+            None => self.local.is_none() || node.lookup_namespace_uri(None) == tag_name.namespace(),
+        }
+    }
+
+    fn matches_attribute(&self, node: XNode) -> bool {
+        let XNodeName::AttributeName(attribute) = node.name() else {
+            return false;
+        };
+        self.local.as_deref().is_none_or(|l| l == attribute.name())
+            && match &self.prefix {
+                Some(prefix) => {
+                    let namespace = node.lookup_namespace_uri(Some(prefix));
+                    namespace.is_some() && namespace == attribute.namespace()
+                }
+                None => self.local.is_none() || attribute.namespace().is_none(),
+            }
+    }
+}
+
+/// Get the element and text children of a node, or the root element for the document.
+fn children<'a, 'doc>(
+    node: Option<XNode<'a, 'doc>>,
+    root: XNode<'a, 'doc>,
+) -> Vec<XNode<'a, 'doc>> {
+    match node {
+        Some(node) => node.child_nodes(),
+        None => vec![root],
+    }
+}
+
+/// Find the `]` closing a predicate, outside the quoted values.
+fn predicate_end(rest: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in rest.char_indices() {
+        match (quote, c) {
+            (None, ']') => return Some(i),
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            _ => {}
+        }
+    }
+    None
+}
+
+fn invalid(xpath: &str) -> XTreeError {
+    XTreeError::InvalidXPath(format!("unsupported step or predicate in {xpath:?}"))
+}
+
+/// Parse a name test at the start of `rest`: `name`, `prefix:name`, `prefix:*` or `*`.
+fn parse_name(rest: &mut &str) -> Option<NameTest> {
+    let end = rest
+        .find(|c: char| !(c.is_alphanumeric() || "_-.:*".contains(c)))
+        .unwrap_or(rest.len());
+    let (name, r) = rest.split_at(end);
+    *rest = r;
+    let (prefix, local) = match name.split_once(':') {
+        Some((prefix, local)) => (Some(prefix), local),
+        None => (None, name),
+    };
+    let valid = |s: &str| !s.is_empty() && !s.contains(['*', ':']);
+    if prefix.is_some_and(|p| !valid(p)) || !(local == "*" || valid(local)) {
+        return None;
+    }
+    Some(NameTest {
+        prefix: prefix.map(str::to_string),
+        local: (local != "*").then(|| local.to_string()),
+    })
+}
+
+/// Parse the content of a predicate: a position, `@name` or `@name='value'`.
+fn parse_predicate(predicate: &str) -> Option<Predicate> {
+    let predicate = predicate.trim();
+    if let Ok(position) = predicate.parse::<usize>() {
+        return (position > 0).then_some(Predicate::Position(position));
+    }
+    let mut rest = predicate.strip_prefix('@')?;
+    let name = parse_name(&mut rest)?;
+    let rest = rest.trim();
+    if rest.is_empty() {
+        return Some(Predicate::Attribute { name, value: None });
+    }
+    let literal = rest.strip_prefix('=')?.trim();
+    let quote = literal.chars().next().filter(|c| *c == '\'' || *c == '"')?;
+    let value = literal[1..].strip_suffix(quote)?;
+    if value.contains(quote) {
+        return None;
+    }
+    Some(Predicate::Attribute {
+        name,
+        value: Some(value.to_string()),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_select() {
+        let text = std::fs::read_to_string("test/file1.xml").unwrap();
+        let tree = XTree::parse(&text).unwrap();
+        let values = |xpath: &str| -> Vec<String> {
+            tree.select(xpath)
+                .unwrap()
+                .iter()
+                .map(|n| n.value().unwrap_or("").trim().to_string())
+                .collect()
+        };
+        assert_eq!(
+            values("/Profile/Customer/Address[2]/StreetNmbr/text()"),
+            ["1200 Yakima St"]
+        );
+        assert_eq!(values("//StreetNmbr/@POBox"), ["4321-01", "4321-01"]);
+        assert_eq!(
+            values("Profile//Address/CityName/text()"),
+            ["Seattle", "Seattle"]
+        );
+        assert_eq!(values("//*[@PhoneUse='Work']/@PhoneTech"), ["Voice"]);
+        assert_eq!(
+            values("//PersonName[@NameType]/GivenName/text()"),
+            ["George"]
+        );
+        assert!(tree.select("//Address[3]").unwrap().is_empty());
+        assert!(tree.select("/Customer").unwrap().is_empty());
+
+        // the XPath of every node selects it
+        let mut nodes = vec![tree.root()];
+        while let Some(node) = nodes.pop() {
+            assert_eq!(tree.select(&node.xpath()).unwrap(), [node]);
+            nodes.extend(node.children());
+        }
+    }
+
+    #[test]
+    fn test_select_namespaces() {
+        let tree = XTree::parse(
+            r#"<a xmlns="urn:d" xmlns:n="urn:n"><b n:x="1"/><n:b x="2"/><c xmlns="" n:x="3"/></a>"#,
+        )
+        .unwrap();
+        let count = |xpath| tree.select(xpath).unwrap().len();
+        assert_eq!(count("/a/b"), 1);
+        assert_eq!(count("/a/n:b"), 1);
+        assert_eq!(count("/a/n:*"), 1);
+        assert_eq!(count("/a/*"), 3);
+        assert_eq!(count("//@n:x"), 2);
+        assert_eq!(count("//@x"), 1);
+        assert_eq!(count("//c"), 1);
+        assert_eq!(count("/a/m:b"), 0);
+        assert_eq!(count("/a/c/@m:x"), 0);
+
+        let tree = XTree::parse("<a><b/></a>").unwrap();
+        assert_eq!(tree.select("/a/m:b").unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_select_quoted_bracket() {
+        let tree = XTree::parse(r#"<a><b k="a]b"/><b k='x"]'/><b k="c"/></a>"#).unwrap();
+        let count = |xpath| tree.select(xpath).unwrap().len();
+        assert_eq!(count(r#"/a/b[@k="a]b"]"#), 1);
+        assert_eq!(count(r#"/a/b[@k='x"]'][1]"#), 1);
+        assert_eq!(count(r#"/a/b[@k="a]"]"#), 0);
+    }
+
+    #[test]
+    fn test_matches() {
+        // the same nodes as selected from the root
+        let text = std::fs::read_to_string("test/file1.xml").unwrap();
+        let tree = XTree::parse(&text).unwrap();
+        let mut nodes = vec![tree.root()];
+        let mut all = Vec::new();
+        while let Some(node) = nodes.pop() {
+            all.push(node);
+            nodes.extend(node.children());
+        }
+        for xpath in [
+            "/Profile/Customer/Address[2]/StreetNmbr/text()",
+            "//StreetNmbr/@POBox",
+            "Profile//Address/CityName/text()",
+            "//*[@PhoneUse='Work']/@PhoneTech",
+            "//PersonName[@NameType]/GivenName/text()",
+            "//Address[1]//text()",
+            "Profile",
+            "/Customer",
+            "//*",
+            "//@*",
+        ] {
+            let xpath = XPath::parse(xpath).unwrap();
+            let selected = xpath.select(&tree);
+            for node in &all {
+                assert_eq!(
+                    xpath.matches(*node),
+                    selected.contains(node),
+                    "{xpath:?} {}",
+                    node.xpath()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_xpath() {
+        for xpath in [
+            "", "/a/", "/a[", "/a[0]", "/a[@x=1]", "/a/b()", "/a//[1]", "/a:", "/a[@x=']",
+        ] {
+            assert!(
+                matches!(XPath::parse(xpath), Err(XTreeError::InvalidXPath(_))),
+                "{xpath}"
+            );
+        }
+    }
+}
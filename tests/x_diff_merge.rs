@@ -0,0 +1,101 @@
+#![cfg(feature = "cli")]
+
+use std::{
+    fs,
+    path::PathBuf,
+    process::{Command, Output},
+};
+
+/// Run `x-diff-merge` and return its exit code and standard error.
+fn x_diff_merge(args: &[&str]) -> (i32, String) {
+    let Output { status, stderr, .. } = Command::new(env!("CARGO_BIN_EXE_x-diff-merge"))
+        .args(args)
+        .output()
+        .unwrap();
+    (status.code().unwrap(), String::from_utf8(stderr).unwrap())
+}
+
+/// Write the base, ours and theirs files of a merge in a new directory, and return their paths.
+fn merge_files(name: &str, base: &str, ours: &str, theirs: &str) -> (PathBuf, [String; 3]) {
+    let root = std::env::temp_dir().join(format!("x-diff-rs-merge-{name}-{}", std::process::id()));
+    if root.exists() {
+        fs::remove_dir_all(&root).unwrap();
+    }
+    fs::create_dir_all(&root).unwrap();
+    let paths = [
+        ("base.xml", base),
+        ("ours.xml", ours),
+        ("theirs.xml", theirs),
+    ]
+    .map(|(file, text)| {
+        let path = root.join(file);
+        fs::write(&path, text).unwrap();
+        path.to_str().unwrap().to_string()
+    });
+    (root, paths)
+}
+
+#[test]
+fn test_merge_clean() {
+    let (root, [base, ours, theirs]) = merge_files(
+        "clean",
+        "<a>\n  <b x=\"1\"/>\n</a>",
+        "<a>\n  <b x=\"1\"/>\n  <c/>\n</a>",
+        "<a>\n  <b x=\"2\"/>\n</a>",
+    );
+    assert_eq!(x_diff_merge(&[&base, &ours, &theirs]), (0, String::new()));
+    assert_eq!(
+        fs::read_to_string(&ours).unwrap(),
+        "<a>\n  <b x=\"2\"/>\n  <c/>\n</a>"
+    );
+    // the other files are kept
+    assert_eq!(
+        fs::read_to_string(&theirs).unwrap(),
+        "<a>\n  <b x=\"2\"/>\n</a>"
+    );
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_merge_conflict() {
+    let (root, [base, ours, theirs]) = merge_files(
+        "conflict",
+        r#"<a><b x="1"/></a>"#,
+        r#"<a><b x="2"/></a>"#,
+        r#"<a><b x="3"/></a>"#,
+    );
+    let (code, stderr) = x_diff_merge(&[&base, &ours, &theirs]);
+    assert_eq!(code, 1);
+    assert!(stderr.starts_with(&format!("{ours}: ")), "{stderr}");
+    let merged = fs::read_to_string(&ours).unwrap();
+    assert!(
+        merged.contains("<<<<<<<") && merged.contains(">>>>>>>"),
+        "{merged}"
+    );
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_merge_errors() {
+    let (code, stderr) = x_diff_merge(&["base.xml", "ours.xml"]);
+    assert_eq!(code, 2);
+    assert_eq!(stderr, "usage: x-diff-merge <base> <ours> <theirs>\n");
+
+    let (root, [base, ours, theirs]) = merge_files("errors", "<a/>", "<a><b/></a>", "<a>");
+    let (code, stderr) = x_diff_merge(&[&base, &ours, &theirs]);
+    assert_eq!(code, 2);
+    assert!(
+        stderr.starts_with(&format!("x-diff-merge: {theirs}: ")),
+        "{stderr}"
+    );
+    // nothing is written on errors
+    assert_eq!(fs::read_to_string(&ours).unwrap(), "<a><b/></a>");
+
+    let (code, stderr) = x_diff_merge(&[&base, &ours, "missing.xml"]);
+    assert_eq!(code, 2);
+    assert!(
+        stderr.starts_with("x-diff-merge: missing.xml: "),
+        "{stderr}"
+    );
+    fs::remove_dir_all(&root).unwrap();
+}
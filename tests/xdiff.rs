@@ -0,0 +1,371 @@
+#![cfg(feature = "cli")]
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+/// Run `xdiff` and return its exit code, standard output and standard error.
+fn xdiff(args: &[&str]) -> (i32, String, String) {
+    let Output {
+        status,
+        stdout,
+        stderr,
+    } = Command::new(env!("CARGO_BIN_EXE_xdiff"))
+        .args(args)
+        .output()
+        .unwrap();
+    (
+        status.code().unwrap(),
+        String::from_utf8(stdout).unwrap(),
+        String::from_utf8(stderr).unwrap(),
+    )
+}
+
+/// Create an empty directory for a test, with the given files.
+fn scratch_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("x-diff-rs-xdiff-{name}-{}", std::process::id()));
+    if root.exists() {
+        fs::remove_dir_all(&root).unwrap();
+    }
+    for (path, text) in files {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, text).unwrap();
+    }
+    fs::create_dir_all(&root).unwrap();
+    root
+}
+
+fn path(root: &Path, name: &str) -> String {
+    root.join(name).to_str().unwrap().to_string()
+}
+
+#[test]
+fn test_usage_errors() {
+    for (args, error) in [
+        (&[][..], "xdiff: expected 2 arguments"),
+        (&["a.xml"], "xdiff: expected 2 arguments"),
+        (
+            &["--git", "a.xml", "b.xml"],
+            "xdiff: expected 1, 7 or 9 arguments",
+        ),
+        (&["--textconv"], "xdiff: expected 1 arguments"),
+        (&["--format", "yaml", "a", "b"], "xdiff: --format expects"),
+        (&["--color"], "xdiff: --color expects"),
+        (&["--ignore"], "xdiff: --ignore expects a glob pattern"),
+        (
+            &["--tolerance", "abc", "a", "b"],
+            "xdiff: --tolerance expects a non-negative number",
+        ),
+        (
+            &["--relative-tolerance", "-0.1", "a", "b"],
+            "xdiff: --relative-tolerance expects a non-negative number",
+        ),
+        (&["--bogus", "a", "b"], "xdiff: unknown option --bogus"),
+    ] {
+        let (code, stdout, stderr) = xdiff(args);
+        assert_eq!(code, 2, "{args:?}");
+        assert!(stdout.is_empty(), "{args:?}");
+        assert!(stderr.starts_with(error), "{args:?}: {stderr}");
+        assert!(stderr.contains("usage: xdiff"), "{args:?}");
+    }
+
+    let (code, stdout, _) = xdiff(&["--help"]);
+    assert_eq!(code, 0);
+    assert!(stdout.starts_with("usage: xdiff"));
+
+    let (code, _, stderr) = xdiff(&["missing1.xml", "missing2.xml"]);
+    assert_eq!(code, 2);
+    assert!(stderr.starts_with("xdiff: missing1.xml: "), "{stderr}");
+}
+
+#[test]
+fn test_diff_files() {
+    let root = scratch_dir(
+        "files",
+        &[
+            ("a.xml", r#"<a><b x="1"/><c/></a>"#),
+            ("b.xml", r#"<a><c/><b x="2"/></a>"#),
+            ("c.xml", r#"<a><c/><b x="1"/></a>"#),
+            ("invalid.xml", "<a>"),
+        ],
+    );
+    let (a, b, c) = (
+        path(&root, "a.xml"),
+        path(&root, "b.xml"),
+        path(&root, "c.xml"),
+    );
+
+    assert_eq!(xdiff(&[&a, &c]), (0, String::new(), String::new()));
+    let (code, stdout, _) = xdiff(&["-e", &a, &b]);
+    assert_eq!(code, 1);
+    assert_eq!(stdout, "update node /a/b/@x: \"1\" -> \"2\"\n");
+    let (code, stdout, _) = xdiff(&["--ordered", "-e", &a, &c]);
+    assert_eq!(code, 1);
+    assert_eq!(stdout, "reorder node /a/b from position 0 to position 1\n");
+
+    let (code, stdout, _) = xdiff(&["--format", "xml-patch", &a, &b]);
+    assert_eq!(code, 1);
+    assert_eq!(
+        stdout,
+        "<diff>\n  <replace sel=\"/a/b[1]/@x\">2</replace>\n</diff>\n"
+    );
+
+    let (code, _, stderr) = xdiff(&[&a, &path(&root, "invalid.xml")]);
+    assert_eq!(code, 2);
+    assert!(stderr.contains("invalid.xml: "), "{stderr}");
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_git_external_diff() {
+    let root = scratch_dir(
+        "git",
+        &[("old.xml", "<a><b/></a>"), ("new.xml", "<a><b/><c/></a>")],
+    );
+    let (old, new) = (path(&root, "old.xml"), path(&root, "new.xml"));
+    let git_args = [
+        "doc.xml", &old, "1234567", "100644", &new, "89abcde", "100644",
+    ];
+    let expected = "xdiff a/doc.xml b/doc.xml\ninsert node /a/c to node /a at position 1\n";
+
+    // the 7 arguments of GIT_EXTERNAL_DIFF, with the exit code 0 for different files
+    let (code, stdout, _) = xdiff(&[&["-e"][..], &git_args].concat());
+    assert_eq!((code, stdout.as_str()), (0, expected));
+    let (code, stdout, _) = xdiff(&[&["--git", "-e"][..], &git_args].concat());
+    assert_eq!((code, stdout.as_str()), (0, expected));
+
+    // an added file
+    let git_args = ["doc.xml", "/dev/null", ".", ".", &new, "89abcde", "100644"];
+    let (code, stdout, _) = xdiff(&[&["-e"][..], &git_args].concat());
+    assert_eq!(code, 0);
+    assert_eq!(
+        stdout,
+        format!("xdiff a/doc.xml b/doc.xml\ninsert document {new}\n")
+    );
+    fs::remove_dir_all(&root).unwrap();
+}
+
+/// Run git in a directory, without the user configuration, and return its standard output.
+fn git(root: &Path, args: &[&str], env: &[(&str, &str)]) -> String {
+    let Output { status, stdout, .. } = Command::new("git")
+        .args([
+            "-c",
+            "user.name=xdiff",
+            "-c",
+            "user.email=xdiff@example.com",
+        ])
+        .args(args)
+        .current_dir(root)
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("GIT_CONFIG_GLOBAL", "/dev/null")
+        .envs(env.iter().copied())
+        .output()
+        .unwrap();
+    assert!(status.success(), "git {args:?}");
+    String::from_utf8(stdout).unwrap()
+}
+
+#[test]
+fn test_git_rename() {
+    let old = "<a>\n  <b/>\n  <c/>\n  <d/>\n  <e/>\n</a>\n";
+    let root = scratch_dir("git-rename", &[("old.xml", old)]);
+    git(&root, &["init", "-q"], &[]);
+    git(&root, &["add", "old.xml"], &[]);
+    git(&root, &["commit", "-q", "-m", "old"], &[]);
+    git(&root, &["mv", "old.xml", "new.xml"], &[]);
+    let new = old.replace("<e/>", "<e/>\n  <f/>");
+    fs::write(root.join("new.xml"), new).unwrap();
+    git(&root, &["add", "new.xml"], &[]);
+
+    // git gives the new path and a similarity message to the external diff of a renamed file
+    let external_diff = format!("{} -e", env!("CARGO_BIN_EXE_xdiff"));
+    let stdout = git(
+        &root,
+        &["diff", "--cached", "-M", "--ext-diff"],
+        &[("GIT_EXTERNAL_DIFF", &external_diff)],
+    );
+    assert_eq!(
+        stdout,
+        "xdiff a/old.xml b/new.xml\ninsert node /a/f to node /a at position 4\n"
+    );
+
+    // an unmerged path
+    let (code, stdout, _) = xdiff(&["--git", "doc.xml"]);
+    assert_eq!((code, stdout.as_str()), (0, "Unmerged path doc.xml\n"));
+    let output = Command::new(env!("CARGO_BIN_EXE_xdiff"))
+        .arg("doc.xml")
+        .env("GIT_DIFF_PATH_TOTAL", "1")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"Unmerged path doc.xml\n");
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_dev_null() {
+    let root = scratch_dir("dev-null", &[("a.xml", "<a><b/></a>")]);
+    let a = path(&root, "a.xml");
+
+    let (code, stdout, _) = xdiff(&["-e", "/dev/null", &a]);
+    assert_eq!((code, stdout), (1, format!("insert document {a}\n")));
+    let (code, stdout, _) = xdiff(&["-e", &a, "/dev/null"]);
+    assert_eq!((code, stdout), (1, format!("delete document {a}\n")));
+    assert_eq!(
+        xdiff(&["/dev/null", "/dev/null"]),
+        (0, String::new(), String::new())
+    );
+
+    let (code, stdout, _) = xdiff(&["--format", "json", &a, "/dev/null"]);
+    assert_eq!(code, 1);
+    let json: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(json.as_array().unwrap().len(), 1);
+    assert_eq!(json[0]["kind"], "delete");
+    assert_eq!(json[0]["old"]["xpath"], "/a");
+    assert_eq!(json[0]["new"], serde_json::Value::Null);
+    let (code, stdout, _) = xdiff(&["--format", "json", "/dev/null", &a]);
+    assert_eq!(code, 1);
+    let json: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(json[0]["kind"], "insert");
+    assert_eq!(json[0]["new"]["xpath"], "/a");
+    assert_eq!(json[0]["position"], 0);
+
+    let (code, _, stderr) = xdiff(&["--format", "xml-patch", "/dev/null", &a]);
+    assert_eq!(code, 2);
+    assert_eq!(
+        stderr,
+        "xdiff: an XML patch cannot add or remove a whole document\n"
+    );
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_textconv() {
+    let root = scratch_dir(
+        "textconv",
+        &[(
+            "a.xml",
+            r#"<a xmlns:n="urn:n" y="2" n:x="1"><c><d/></c> t <b/></a>"#,
+        )],
+    );
+    let a = path(&root, "a.xml");
+
+    let (code, stdout, _) = xdiff(&["--textconv", &a]);
+    assert_eq!(code, 0);
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        [
+            "<a>",
+            r#"  "t""#,
+            "  <b>",
+            "  <c>",
+            "    <d>",
+            r#"  @y="2""#,
+            r#"  @{urn:n}x="1""#,
+        ]
+    );
+    let (code, stdout, _) = xdiff(&["--textconv", "--ordered", &a]);
+    assert_eq!(code, 0);
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        [
+            "<a>",
+            r#"  @y="2""#,
+            r#"  @{urn:n}x="1""#,
+            "  <c>",
+            "    <d>",
+            r#"  "t""#,
+            "  <b>",
+        ]
+    );
+
+    assert_eq!(
+        xdiff(&["--textconv", "/dev/null"]),
+        (0, String::new(), String::new())
+    );
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_directories() {
+    let root = scratch_dir(
+        "dirs",
+        &[
+            ("1/same.xml", "<a><b/><c/></a>"),
+            ("2/same.xml", "<a><c/><b/></a>"),
+            ("1/sub/changed.xml", "<a>1</a>"),
+            ("2/sub/changed.xml", "<a>2</a>"),
+            ("1/removed.xml", "<a/>"),
+            ("2/added.xml", "<a/>"),
+        ],
+    );
+    let (dir1, dir2) = (path(&root, "1"), path(&root, "2"));
+
+    let (code, stdout, _) = xdiff(&[&dir1, &dir2]);
+    assert_eq!(code, 1);
+    let lines: Vec<_> = stdout.lines().collect();
+    for name in ["added.xml", "removed.xml", "changed.xml"] {
+        assert!(lines.iter().any(|line| line.contains(name)), "{stdout}");
+    }
+    assert!(!stdout.contains("same.xml"), "{stdout}");
+
+    let (code, _, _) = xdiff(&[&dir1, &dir1]);
+    assert_eq!(code, 0);
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_ignore() {
+    let root = scratch_dir(
+        "ignore",
+        &[
+            ("a.xml", r#"<a timestamp="1"><b timestamp="2">x</b></a>"#),
+            ("b.xml", r#"<a timestamp="3"><b timestamp="4">x</b></a>"#),
+        ],
+    );
+    let (a, b) = (path(&root, "a.xml"), path(&root, "b.xml"));
+
+    let (code, stdout, _) = xdiff(&["-e", &a, &b]);
+    assert_eq!((code, stdout.lines().count()), (1, 2));
+    assert_eq!(
+        xdiff(&["--ignore", "**/@timestamp", &a, &b]),
+        (0, String::new(), String::new())
+    );
+    let (code, stdout, _) = xdiff(&["-e", "--ignore", "/a/@timestamp", &a, &b]);
+    assert_eq!(code, 1);
+    assert_eq!(stdout, "update node /a/b/@timestamp: \"2\" -> \"4\"\n");
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_tolerance() {
+    let root = scratch_dir(
+        "tolerance",
+        &[
+            ("a.xml", r#"<a x="1.0"><b>2.5</b></a>"#),
+            ("b.xml", r#"<a x="1.01"><b>2.46</b></a>"#),
+        ],
+    );
+    let (a, b) = (path(&root, "a.xml"), path(&root, "b.xml"));
+
+    let (code, stdout, _) = xdiff(&["-e", &a, &b]);
+    assert_eq!((code, stdout.lines().count()), (1, 2));
+    assert_eq!(
+        xdiff(&["--tolerance", "0.05", &a, &b]),
+        (0, String::new(), String::new())
+    );
+    let (code, stdout, _) = xdiff(&["-e", "--tolerance", "0.02", &a, &b]);
+    assert_eq!(code, 1);
+    assert_eq!(stdout, "update node /a/b/text(): \"2.5\" -> \"2.46\"\n");
+    assert_eq!(
+        xdiff(&["--relative-tolerance", "0.02", &a, &b]),
+        (0, String::new(), String::new())
+    );
+    let (code, stdout, _) = xdiff(&["-e", "--relative-tolerance", "0.01", &a, &b]);
+    assert_eq!(code, 1);
+    assert_eq!(stdout, "update node /a/b/text(): \"2.5\" -> \"2.46\"\n");
+    fs::remove_dir_all(&root).unwrap();
+}